@@ -0,0 +1,431 @@
+//! `#[derive(Randr)]` for casec's `randr` module.
+//!
+//! The generated code refers to `crate::randr`, so the derive is only usable from within the
+//! casec crate itself.
+//!
+//! - Structs generate every field with its own `Randr` impl.
+//! - Enums made only of unit variants also get a `Variants` impl (so they must be `Copy`) and
+//!   are sampled through the generation context, honouring any configured weights.
+//! - Enums carrying data pick a variant uniformly and generate its fields.
+//!
+//! Fields accept `#[randr(...)]` overrides:
+//!
+//! - `len = 64` generates an alphanumeric string of that length
+//! - `range = 0..100000` samples the value from the range
+//! - `null_rate = 0.3` sets the probability of an `Option` field being `None`, from 0.0 to 1.0
+//! - `with = path::to::generator` calls `fn(&mut Context) -> T` to generate the value
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::parse::ParseStream;
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Data, DeriveInput, Expr, Fields, GenericArgument, LitFloat, LitInt,
+    PathArguments, Type,
+};
+
+#[proc_macro_derive(Randr, attributes(randr))]
+pub fn derive_randr(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = generate_fields(&data.fields)?;
+            quote!(Self #fields)
+        }
+        Data::Enum(data) if data.variants.is_empty() => {
+            return Err(syn::Error::new(
+                name.span(),
+                "Randr cannot be derived for enums without variants",
+            ))
+        }
+        Data::Enum(data)
+            if data
+                .variants
+                .iter()
+                .all(|variant| matches!(variant.fields, Fields::Unit)) =>
+        {
+            let variants = data.variants.iter().map(|variant| &variant.ident);
+
+            return Ok(quote! {
+                impl #impl_generics crate::randr::Variants for #name #ty_generics #where_clause {
+                    const VARIANTS: &'static [Self] = &[#(Self::#variants),*];
+                }
+
+                impl #impl_generics crate::randr::Randr for #name #ty_generics #where_clause {
+                    fn default(ctx: &mut crate::randr::Context) -> Self {
+                        ctx.sample()
+                    }
+                }
+            });
+        }
+        Data::Enum(data) => {
+            let count = data.variants.len();
+            let arms = data
+                .variants
+                .iter()
+                .enumerate()
+                .map(|(index, variant)| {
+                    let ident = &variant.ident;
+                    let fields = generate_fields(&variant.fields)?;
+                    Ok(quote!(#index => Self::#ident #fields,))
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+
+            quote! {
+                match crate::randr::range(ctx, 0..#count) {
+                    #(#arms)*
+                    _ => unreachable!(),
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                name.span(),
+                "Randr cannot be derived for unions",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics crate::randr::Randr for #name #ty_generics #where_clause {
+            fn default(ctx: &mut crate::randr::Context) -> Self {
+                #body
+            }
+        }
+    })
+}
+
+fn generate_fields(fields: &Fields) -> syn::Result<TokenStream> {
+    match fields {
+        Fields::Named(fields) => {
+            let fields = fields
+                .named
+                .iter()
+                .map(|field| {
+                    let ident = &field.ident;
+                    let value = generate_value(field)?;
+                    Ok(quote!(#ident: #value))
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+
+            Ok(quote!({ #(#fields),* }))
+        }
+        Fields::Unnamed(fields) => {
+            let fields = fields
+                .unnamed
+                .iter()
+                .map(generate_value)
+                .collect::<syn::Result<Vec<_>>>()?;
+
+            Ok(quote!(( #(#fields),* )))
+        }
+        Fields::Unit => Ok(TokenStream::new()),
+    }
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    len: Option<LitInt>,
+    range: Option<Expr>,
+    null_rate: Option<LitFloat>,
+    with: Option<syn::Path>,
+}
+
+impl FieldOptions {
+    fn from_field(field: &syn::Field) -> syn::Result<Self> {
+        let mut options = Self::default();
+
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("randr"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("len") {
+                    options.len = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("range") {
+                    options.range = Some(meta.value()?.call(parse_range)?);
+                } else if meta.path.is_ident("with") {
+                    options.with = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("null_rate") {
+                    let null_rate: LitFloat = meta.value()?.parse()?;
+                    if !(0.0..=1.0).contains(&null_rate.base10_parse::<f64>()?) {
+                        return Err(syn::Error::new(
+                            null_rate.span(),
+                            "`null_rate` must be between 0.0 and 1.0",
+                        ));
+                    }
+
+                    options.null_rate = Some(null_rate);
+                } else {
+                    return Err(meta.error("expected `len`, `range`, `with` or `null_rate`"));
+                }
+
+                Ok(())
+            })?;
+        }
+
+        let generators = [
+            options.len.is_some(),
+            options.range.is_some(),
+            options.with.is_some(),
+        ];
+        if generators.into_iter().filter(|set| *set).count() > 1 {
+            return Err(syn::Error::new(
+                field.span(),
+                "only one of `len`, `range` and `with` can be set",
+            ));
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_range(input: ParseStream) -> syn::Result<Expr> {
+    match input.parse()? {
+        range @ Expr::Range(_) => Ok(range),
+        expr => Err(syn::Error::new(
+            expr.span(),
+            "expected a range such as `0..100`",
+        )),
+    }
+}
+
+fn generate_value(field: &syn::Field) -> syn::Result<TokenStream> {
+    let options = FieldOptions::from_field(field)?;
+
+    let inner = match (&options.len, &options.range, &options.with) {
+        (Some(len), _, _) => Some(quote!(crate::randr::string(ctx, #len))),
+        (_, Some(range), _) => Some(quote!(crate::randr::range(ctx, #range))),
+        (_, _, Some(with)) => Some(quote!(#with(ctx))),
+        _ => None,
+    };
+
+    match (is_option(&field.ty), inner, options.null_rate) {
+        (true, inner, null_rate) if inner.is_some() || null_rate.is_some() => {
+            let inner = inner.unwrap_or_else(|| quote!(crate::randr::Randr::default(ctx)));
+            let null_rate = null_rate
+                .map(ToTokens::into_token_stream)
+                .unwrap_or_else(|| quote!(0.5));
+
+            Ok(quote!(crate::randr::nullable(ctx, #null_rate, |ctx| #inner)))
+        }
+        (false, _, Some(null_rate)) => Err(syn::Error::new(
+            null_rate.span(),
+            "`null_rate` is only supported on `Option` fields",
+        )),
+        (_, Some(inner), _) => Ok(inner),
+        (_, None, _) => Ok(quote!(crate::randr::Randr::randr(ctx, None, None))),
+    }
+}
+
+/// Whether the field is an `Option<T>`, matched on the last path segment.
+fn is_option(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+
+    match path.path.segments.last() {
+        Some(segment) if segment.ident == "Option" => matches!(
+            &segment.arguments,
+            PathArguments::AngleBracketed(args)
+                if args.args.len() == 1 && matches!(args.args[0], GenericArgument::Type(_))
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syn::parse_quote;
+
+    use super::*;
+
+    fn error(input: DeriveInput) -> String {
+        expand(input).unwrap_err().to_string()
+    }
+
+    fn assert_expands(input: DeriveInput, expected: TokenStream) {
+        assert_eq!(expand(input).unwrap().to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn struct_generates_each_field() {
+        let input = parse_quote! {
+            struct Attempt {
+                #[randr(len = 64)]
+                payment_id: String,
+                #[randr(range = 0..100)]
+                amount: i64,
+                #[randr(with = generate::metadata)]
+                metadata: Value,
+                #[randr(null_rate = 0.3)]
+                connector: Option<String>,
+                #[randr(len = 8, null_rate = 0.1)]
+                charge_id: Option<String>,
+                #[randr(range = 0..10)]
+                count: Option<i16>,
+                status: Status,
+            }
+        };
+
+        assert_expands(
+            input,
+            quote! {
+                impl crate::randr::Randr for Attempt {
+                    fn default(ctx: &mut crate::randr::Context) -> Self {
+                        Self {
+                            payment_id: crate::randr::string(ctx, 64),
+                            amount: crate::randr::range(ctx, 0..100),
+                            metadata: generate::metadata(ctx),
+                            connector: crate::randr::nullable(ctx, 0.3, |ctx| crate::randr::Randr::default(ctx)),
+                            charge_id: crate::randr::nullable(ctx, 0.1, |ctx| crate::randr::string(ctx, 8)),
+                            count: crate::randr::nullable(ctx, 0.5, |ctx| crate::randr::range(ctx, 0..10)),
+                            status: crate::randr::Randr::randr(ctx, None, None)
+                        }
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn unit_enum_is_sampled_through_the_context() {
+        let input = parse_quote! {
+            enum Status {
+                Started,
+                Charged,
+            }
+        };
+
+        assert_expands(
+            input,
+            quote! {
+                impl crate::randr::Variants for Status {
+                    const VARIANTS: &'static [Self] = &[Self::Started, Self::Charged];
+                }
+
+                impl crate::randr::Randr for Status {
+                    fn default(ctx: &mut crate::randr::Context) -> Self {
+                        ctx.sample()
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn data_enum_picks_a_variant_uniformly() {
+        let input = parse_quote! {
+            enum Mandate {
+                SingleUse(Amount),
+                MultiUse { amount: Option<Amount> },
+            }
+        };
+
+        assert_expands(
+            input,
+            quote! {
+                impl crate::randr::Randr for Mandate {
+                    fn default(ctx: &mut crate::randr::Context) -> Self {
+                        match crate::randr::range(ctx, 0..2usize) {
+                            0usize => Self::SingleUse(crate::randr::Randr::randr(ctx, None, None)),
+                            1usize => Self::MultiUse { amount: crate::randr::Randr::randr(ctx, None, None) },
+                            _ => unreachable!(),
+                        }
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn unsupported_inputs_are_rejected() {
+        assert_eq!(
+            error(parse_quote!(
+                enum Never {}
+            )),
+            "Randr cannot be derived for enums without variants"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                union Bits {
+                    int: u32,
+                    float: f32,
+                }
+            )),
+            "Randr cannot be derived for unions"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                struct Attempt {
+                    #[randr(len = 8, range = 0..8)]
+                    payment_id: String,
+                }
+            )),
+            "only one of `len`, `range` and `with` can be set"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                struct Attempt {
+                    #[randr(null_rate = 0.5)]
+                    payment_id: String,
+                }
+            )),
+            "`null_rate` is only supported on `Option` fields"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                struct Attempt {
+                    #[randr(length = 8)]
+                    payment_id: String,
+                }
+            )),
+            "expected `len`, `range`, `with` or `null_rate`"
+        );
+        assert_eq!(
+            error(parse_quote!(
+                struct Attempt {
+                    #[randr(range = 8)]
+                    amount: i64,
+                }
+            )),
+            "expected a range such as `0..100`"
+        );
+    }
+
+    #[test]
+    fn null_rate_must_be_a_probability() {
+        for null_rate in [quote!(1.5), quote!(-0.1)] {
+            let input = parse_quote! {
+                struct Attempt {
+                    #[randr(null_rate = #null_rate)]
+                    connector: Option<String>,
+                }
+            };
+
+            assert_eq!(error(input), "`null_rate` must be between 0.0 and 1.0");
+        }
+
+        for null_rate in [quote!(0.0), quote!(1.0)] {
+            let input = parse_quote! {
+                struct Attempt {
+                    #[randr(null_rate = #null_rate)]
+                    connector: Option<String>,
+                }
+            };
+
+            assert!(expand(input).is_ok());
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+use cassandra_cpp::{Batch, BatchType};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use tracing::{field, info_span, Instrument};
+
+use crate::cassandra::Cassandra;
+use crate::query;
+use crate::PaymentAttempt;
+
+/// How `/create/batch` groups its inserts.
+#[derive(Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Every attempt in a single `UNLOGGED` batch
+    #[default]
+    Unlogged,
+    /// Every attempt in a single `LOGGED` batch, which goes through the batch log
+    Logged,
+    /// One `UNLOGGED` batch per `payment_id`, so that the attempts of a batch share a partition.
+    /// Their lookup entries live in partitions of their own
+    Partitioned,
+}
+
+impl Mode {
+    fn batch_type(self) -> BatchType {
+        match self {
+            Self::Logged => BatchType::LOGGED,
+            Self::Unlogged | Self::Partitioned => BatchType::UNLOGGED,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    mode: Mode,
+    attempts: usize,
+    /// Time spent executing all batches, in microseconds
+    total_us: u64,
+    batches: Vec<BatchReport>,
+}
+
+#[derive(Serialize)]
+struct BatchReport {
+    /// Partition the batch was limited to, only set in partitioned mode
+    payment_id: Option<String>,
+    /// Inserts into the main and lookup tables
+    statements: usize,
+    latency_us: u64,
+}
+
+/// Writes the attempts through `insert_query.cql` along with their lookup table entries, batched
+/// according to `mode`. Batches are executed one after the other so that their latencies do not
+/// interfere.
+pub async fn insert(
+    attempts: &[PaymentAttempt],
+    mode: Mode,
+    cassandra: &Cassandra,
+) -> Result<Report, Box<dyn std::error::Error>> {
+    let groups: Vec<(Option<String>, Vec<&PaymentAttempt>)> = match mode {
+        Mode::Unlogged | Mode::Logged => vec![(None, attempts.iter().collect())],
+        Mode::Partitioned => {
+            let mut partitions: BTreeMap<&str, Vec<&PaymentAttempt>> = BTreeMap::new();
+            for attempt in attempts {
+                partitions
+                    .entry(attempt.payment_id.as_str())
+                    .or_default()
+                    .push(attempt);
+            }
+
+            partitions
+                .into_iter()
+                .map(|(payment_id, attempts)| (Some(payment_id.to_string()), attempts))
+                .collect()
+        }
+    };
+
+    let mut batches = Vec::new();
+    let start = Instant::now();
+
+    for (payment_id, attempts) in groups {
+        let (batch, statements) = batch(&attempts, mode, cassandra)?;
+        let consistencies = cassandra.consistencies();
+
+        let batch_start = Instant::now();
+        batch
+            .execute()
+            .instrument(info_span!(
+                "execute",
+                query = "insert_batch",
+                statements,
+                consistency = %consistencies.write,
+                serial_consistency = consistencies.serial.map(field::display),
+            ))
+            .await?;
+
+        batches.push(BatchReport {
+            payment_id,
+            statements,
+            latency_us: micros(batch_start),
+        });
+    }
+
+    Ok(Report {
+        mode,
+        attempts: attempts.len(),
+        total_us: micros(start),
+        batches,
+    })
+}
+
+/// A batch of inserts and the number of statements in it, running with the write consistency
+/// since the consistency of statements within a batch is ignored.
+fn batch(
+    attempts: &[&PaymentAttempt],
+    mode: Mode,
+    cassandra: &Cassandra,
+) -> Result<(Batch, usize), Box<dyn std::error::Error>> {
+    let mut batch = cassandra.session().batch(mode.batch_type());
+
+    let consistencies = cassandra.consistencies();
+    batch.set_consistency(consistencies.write.into())?;
+    if let Some(serial) = consistencies.serial {
+        batch.set_serial_consistency(serial.into())?;
+    }
+
+    let mut statements = 0;
+    for attempt in attempts {
+        let mut statement = query::INSERT.statement(cassandra)?;
+
+        query::INSERT.bind(|| attempt.populate_statement(&mut statement, cassandra.layout()))?;
+
+        batch.add_statement(statement)?;
+        statements += 1;
+
+        for (_, statement) in cassandra.lookup_statements(attempt)? {
+            batch.add_statement(statement)?;
+            statements += 1;
+        }
+    }
+
+    Ok((batch, statements))
+}
+
+fn micros(start: Instant) -> u64 {
+    u64::try_from(start.elapsed().as_micros()).unwrap_or(u64::MAX)
+}
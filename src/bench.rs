@@ -0,0 +1,438 @@
+use std::env;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Context as _;
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::{info, warn};
+
+use crate::memory::MemoryStore;
+use crate::randr::{self, Context};
+use crate::stats::Recorder;
+use crate::store::PaymentAttemptStore;
+use crate::{retrieve_data, Generator, PaymentAttempt};
+
+/// Upper bound on the keys of written attempts that reads are drawn from
+const KEY_POOL_SIZE: usize = 100_000;
+
+/// Load generator, started with `casec bench`. Configured through the environment:
+///
+/// - `BENCH_TARGET`: `http` to go through the server at `BENCH_URL`, `cassandra` to drive a
+///   `Session` directly (default `http`), honouring `LAYOUT` and `PREPARED` like the server does,
+///   `postgres` to drive the Postgres store at `POSTGRES_URL`, or `memory` to run against an
+///   in-process store as a baseline
+/// - `BENCH_QPS`: target rate across all workers, unthrottled when unset or `0`
+/// - `BENCH_CONCURRENCY`: number of workers (default 16)
+/// - `BENCH_DURATION`: run time in seconds (default 60)
+/// - `BENCH_READ_RATIO`: share of point reads, drawn from the attempts written so far
+///   (default 0.5)
+///
+/// Attempts are generated the same way as `/create` does, so `SEED`, `RANDR_WEIGHTS`,
+/// `PAYLOAD_BYTES` and `GENERATOR` apply here as well. The `http` target always uses the
+/// consistent generator, `/create` rejects the negative amounts of random attempts.
+pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::from_env().await?;
+
+    info!(
+        bench_target = config.target.name(),
+        concurrency = config.concurrency,
+        duration_s = config.duration.as_secs(),
+        qps = config
+            .qps
+            .map_or("unthrottled".to_string(), |qps| qps.to_string()),
+        read_ratio = config.read_ratio,
+        "starting bench"
+    );
+
+    let workload = Arc::new(Workload {
+        state: Mutex::new(WorkloadState {
+            ctx: crate::randr_context()?,
+            keys: Vec::new(),
+        }),
+        generator: config.target.generator()?,
+        read_ratio: config.read_ratio,
+    });
+
+    let interval = config
+        .qps
+        .map(|qps| Duration::from_secs_f64(config.concurrency as f64 / qps));
+
+    let start = Instant::now();
+    let deadline = start + config.duration;
+
+    let workers = (0..config.concurrency)
+        .map(|_| {
+            tokio::spawn(worker(
+                config.target.clone(),
+                workload.clone(),
+                interval,
+                deadline,
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let mut report = Report::default();
+    for worker in workers {
+        report.merge(worker.await?);
+    }
+
+    let elapsed = start.elapsed();
+
+    report.writes.summary("write", elapsed);
+    report.reads.summary("read", elapsed);
+
+    Ok(())
+}
+
+struct Config {
+    target: Target,
+    qps: Option<f64>,
+    concurrency: usize,
+    duration: Duration,
+    read_ratio: f64,
+}
+
+impl Config {
+    async fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
+        let target = match env::var("BENCH_TARGET").as_deref() {
+            Ok("http") | Err(_) => Target::Http {
+                client: reqwest::Client::new(),
+                url: env::var("BENCH_URL").unwrap_or("http://localhost:8000".to_string()),
+            },
+            #[cfg(feature = "cassandra")]
+            Ok("cassandra") => Target::Store {
+                name: "cassandra",
+                store: Arc::new(crate::cassandra().await?),
+            },
+            Ok("memory") => Target::Store {
+                name: "memory",
+                store: Arc::new(MemoryStore::default()),
+            },
+            Ok("postgres") => Target::Store {
+                name: "postgres",
+                store: Arc::new(crate::postgres().await?),
+            },
+            Ok(target) => return Err(format!("Unknown BENCH_TARGET: {target}").into()),
+        };
+
+        let read_ratio = parse_env("BENCH_READ_RATIO")?.unwrap_or(0.5);
+        if !(0.0..=1.0).contains(&read_ratio) {
+            return Err("BENCH_READ_RATIO must be between 0 and 1".into());
+        }
+
+        Ok(Self {
+            target,
+            qps: parse_env::<f64>("BENCH_QPS")?.filter(|qps| *qps > 0.0),
+            concurrency: parse_env("BENCH_CONCURRENCY")?.unwrap_or(16).max(1),
+            duration: Duration::from_secs(parse_env("BENCH_DURATION")?.unwrap_or(60)),
+            read_ratio,
+        })
+    }
+}
+
+fn parse_env<T>(name: &str) -> Result<Option<T>, Box<dyn std::error::Error>>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    let value = env::var(name)
+        .ok()
+        .map(|value| value.parse::<T>())
+        .transpose()
+        .with_context(|| format!("Failed while parsing {name}"))?;
+
+    Ok(value)
+}
+
+#[derive(Clone)]
+enum Target {
+    Http {
+        client: reqwest::Client,
+        url: String,
+    },
+    Store {
+        name: &'static str,
+        store: Arc<dyn PaymentAttemptStore>,
+    },
+}
+
+impl Target {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Http { .. } => "http",
+            Self::Store { name, .. } => name,
+        }
+    }
+
+    /// How written attempts are generated. Bodies sent over HTTP are validated by `/create`, so
+    /// they have to respect payment invariants.
+    fn generator(&self) -> Result<Generator, Box<dyn std::error::Error>> {
+        match self {
+            Self::Http { .. } => Ok(Generator::Consistent),
+            Self::Store { .. } => crate::generator(),
+        }
+    }
+
+    async fn write(
+        &self,
+        payment_attempt: PaymentAttempt,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Self::Http { client, url } => {
+                let body = client
+                    .post(format!("{url}/create"))
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(serde_json::to_vec(&payment_attempt)?)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .bytes()
+                    .await?;
+
+                // Failures are answered with a plain message, not a status code
+                serde_json::from_slice::<PaymentAttempt>(&body)?;
+            }
+            Self::Store { store, .. } => {
+                store.insert(payment_attempt).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn read(
+        &self,
+        payment_id: String,
+        attempt_id: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Self::Http { client, url } => {
+                let body = client
+                    .get(format!("{url}/retrieve/{payment_id}/{attempt_id}"))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .bytes()
+                    .await?;
+
+                serde_json::from_slice::<PaymentAttempt>(&body)?;
+            }
+            Self::Store { store, .. } => {
+                retrieve_data(&payment_id, &attempt_id, store.as_ref()).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+enum Operation {
+    Write(Box<PaymentAttempt>),
+    Read(String, String),
+}
+
+struct Workload {
+    state: Mutex<WorkloadState>,
+    generator: Generator,
+    read_ratio: f64,
+}
+
+/// Everything workers share, behind a single lock so there is no lock order to get wrong.
+struct WorkloadState {
+    ctx: Context,
+    keys: Vec<(String, String)>,
+}
+
+impl Workload {
+    fn next(&self) -> Operation {
+        let mut state = self.state.lock().expect("Error while locking the workload");
+        let WorkloadState { ctx, keys } = &mut *state;
+
+        if !keys.is_empty() && randr::range(ctx, 0.0..1.0) < self.read_ratio {
+            let (payment_id, attempt_id) = keys[randr::range(ctx, 0..keys.len())].clone();
+            return Operation::Read(payment_id, attempt_id);
+        }
+
+        Operation::Write(Box::new(self.generator.generate(ctx)))
+    }
+
+    fn remember(&self, key: (String, String)) {
+        let mut state = self.state.lock().expect("Error while locking the workload");
+        let WorkloadState { ctx, keys } = &mut *state;
+
+        match keys.len() < KEY_POOL_SIZE {
+            true => keys.push(key),
+            false => {
+                let index = randr::range(ctx, 0..keys.len());
+                keys[index] = key;
+            }
+        }
+    }
+}
+
+async fn worker(
+    target: Target,
+    workload: Arc<Workload>,
+    interval: Option<Duration>,
+    deadline: Instant,
+) -> Report {
+    let mut ticker = interval.map(|period| {
+        let mut ticker = tokio::time::interval(period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker
+    });
+
+    let mut report = Report::default();
+
+    while Instant::now() < deadline {
+        if let Some(ticker) = &mut ticker {
+            ticker.tick().await;
+        }
+
+        match workload.next() {
+            Operation::Write(payment_attempt) => {
+                let key = (
+                    payment_attempt.payment_id.clone(),
+                    payment_attempt.attempt_id.clone(),
+                );
+
+                let start = Instant::now();
+                let output = target
+                    .write(*payment_attempt)
+                    .await
+                    .map_err(|err| err.to_string());
+                let written = output.is_ok();
+                report.writes.record(start.elapsed(), output);
+
+                // Reads of attempts that were never stored would only count as errors
+                if written {
+                    workload.remember(key);
+                }
+            }
+            Operation::Read(payment_id, attempt_id) => {
+                let start = Instant::now();
+                let output = target
+                    .read(payment_id, attempt_id)
+                    .await
+                    .map_err(|err| err.to_string());
+                report.reads.record(start.elapsed(), output);
+            }
+        }
+    }
+
+    report
+}
+
+#[derive(Default)]
+struct Report {
+    writes: Latencies,
+    reads: Latencies,
+}
+
+impl Report {
+    fn merge(&mut self, other: Self) {
+        self.writes.merge(other.writes);
+        self.reads.merge(other.reads);
+    }
+}
+
+#[derive(Default)]
+struct Latencies {
+    recorder: Recorder,
+    last_error: Option<String>,
+}
+
+impl Latencies {
+    fn record(&mut self, elapsed: Duration, output: Result<(), String>) {
+        self.recorder.record(elapsed, output.is_ok());
+
+        if let Err(err) = output {
+            self.last_error = Some(err);
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.recorder.merge(&other.recorder);
+        self.last_error = other.last_error.or(self.last_error.take());
+    }
+
+    fn summary(&self, name: &str, elapsed: Duration) {
+        let summary = self.recorder.summary();
+
+        info!(
+            operation = name,
+            count = summary.count,
+            errors = summary.errors,
+            qps = format!("{:.1}", summary.count as f64 / elapsed.as_secs_f64()),
+            p50_us = summary.p50,
+            p90_us = summary.p90,
+            p99_us = summary.p99,
+            p999_us = summary.p999,
+            max_us = summary.max,
+            "bench summary"
+        );
+
+        if let Some(err) = &self.last_error {
+            warn!(operation = name, error = %err, "last bench error");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn workers_share_a_full_key_pool() {
+        let keys = (0..KEY_POOL_SIZE)
+            .map(|index| (format!("pay_{index}"), format!("att_{index}")))
+            .collect();
+        let workload = Arc::new(Workload {
+            state: Mutex::new(WorkloadState {
+                ctx: Context::new(Some(3)),
+                keys,
+            }),
+            generator: Generator::Random,
+            read_ratio: 0.5,
+        });
+
+        let workers = (0..4)
+            .map(|_| {
+                let workload = workload.clone();
+                thread::spawn(move || {
+                    for _ in 0..200 {
+                        if let Operation::Write(payment_attempt) = workload.next() {
+                            workload
+                                .remember((payment_attempt.payment_id, payment_attempt.attempt_id));
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        let state = workload.state.lock().unwrap();
+        assert_eq!(state.keys.len(), KEY_POOL_SIZE);
+    }
+
+    #[test]
+    fn http_writes_pass_validation() {
+        let target = Target::Http {
+            client: reqwest::Client::new(),
+            url: "http://localhost:8000".to_string(),
+        };
+        let generator = target.generator().unwrap();
+        let mut ctx = Context::new(Some(5));
+
+        for _ in 0..1000 {
+            let body = serde_json::to_vec(&generator.generate(&mut ctx)).unwrap();
+            PaymentAttempt::from_body(&body).unwrap();
+        }
+    }
+}
@@ -0,0 +1,462 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Context;
+use axum::async_trait;
+use cassandra_cpp::{
+    AsRustType, BindRustType, LendingIterator, PreparedStatement, Session, Statement,
+};
+use tracing::{info_span, Instrument};
+
+use crate::consistency::{Consistencies, Overrides};
+use crate::keyspace::Keyspace;
+use crate::layout::Layout;
+use crate::query::{self, Query};
+use crate::store::PaymentAttemptStore;
+use crate::update::PaymentAttemptUpdate;
+use crate::PaymentAttempt;
+
+/// A session together with the statements prepared on it. Cloning is cheap, clones share both.
+#[derive(Clone)]
+pub struct Cassandra {
+    session: Session,
+    layout: Layout,
+    keyspace: Arc<Keyspace>,
+    /// Static queries keyed by name
+    queries: Arc<HashMap<&'static str, Rendered>>,
+    consistencies: Consistencies,
+    /// Updates only apply to existing attempts, see `Query::text`
+    conditional_updates: bool,
+}
+
+/// A static query rendered for the layout in use.
+struct Rendered {
+    cql: String,
+    /// `None` when running unprepared
+    prepared: Option<PreparedStatement>,
+}
+
+impl Cassandra {
+    /// Prepares every static query up front when `prepare` is set, otherwise statements are sent
+    /// as simple statements and parsed by Cassandra on every request. The tables must exist
+    /// already.
+    pub async fn new(
+        session: Session,
+        layout: Layout,
+        keyspace: Keyspace,
+        prepare: bool,
+        consistencies: Consistencies,
+        conditional_updates: bool,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut queries = HashMap::new();
+
+        for query in query::STATIC {
+            let cql = layout.render(&query.text(conditional_updates), &keyspace);
+            let prepared = match prepare {
+                true => Some(
+                    session
+                        .prepare(&cql)
+                        .instrument(info_span!("prepare", query = query.name()))
+                        .await?,
+                ),
+                false => None,
+            };
+
+            queries.insert(query.name(), Rendered { cql, prepared });
+        }
+
+        Ok(Self {
+            session,
+            layout,
+            keyspace: Arc::new(keyspace),
+            queries: Arc::new(queries),
+            consistencies,
+            conditional_updates,
+        })
+    }
+
+    pub fn session(&self) -> &Session {
+        &self.session
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn keyspace(&self) -> &Keyspace {
+        &self.keyspace
+    }
+
+    pub fn consistencies(&self) -> Consistencies {
+        self.consistencies
+    }
+
+    /// Handle whose statements run with the consistency levels requested by a single request.
+    pub fn with_consistency(&self, overrides: Overrides) -> Self {
+        Self {
+            consistencies: self.consistencies.with(overrides),
+            ..self.clone()
+        }
+    }
+
+    /// Binds a new statement from the prepared statement if there is one, otherwise builds a
+    /// simple statement from the query text. Queries outside `query::STATIC` are taken as they
+    /// are.
+    pub fn statement(&self, query: &Query) -> Statement {
+        match self.queries.get(query.name()) {
+            Some(Rendered {
+                prepared: Some(prepared),
+                ..
+            }) => prepared.bind(),
+            Some(Rendered { cql, .. }) => self.session.statement(cql),
+            None => self.session.statement(query.cql()),
+        }
+    }
+
+    /// Inserts into the lookup tables for `payment_attempt`, bound and ready to execute or batch.
+    pub fn lookup_statements(
+        &self,
+        payment_attempt: &PaymentAttempt,
+    ) -> Result<Vec<(&'static Query, Statement)>, Box<dyn std::error::Error>> {
+        let query = &query::INSERT_BY_MERCHANT;
+        let mut statement = query.statement(self)?;
+
+        query.bind(|| {
+            statement.bind(0, payment_attempt.merchant_id.as_str())?;
+            statement.bind(1, payment_attempt.payment_id.as_str())?;
+            statement.bind(2, payment_attempt.attempt_id.as_str())?;
+            Ok(())
+        })?;
+
+        let mut statements = vec![(query, statement)];
+
+        if let Some(connector_transaction_id) = &payment_attempt.connector_transaction_id {
+            statements.push(self.connector_transaction_statement(
+                &payment_attempt.merchant_id,
+                connector_transaction_id,
+                &payment_attempt.payment_id,
+                &payment_attempt.attempt_id,
+            )?);
+        }
+
+        Ok(statements)
+    }
+
+    fn connector_transaction_statement(
+        &self,
+        merchant_id: &str,
+        connector_transaction_id: &str,
+        payment_id: &str,
+        attempt_id: &str,
+    ) -> Result<(&'static Query, Statement), Box<dyn std::error::Error>> {
+        let query = &query::INSERT_BY_CONNECTOR_TRANSACTION;
+        let mut statement = query.statement(self)?;
+
+        query.bind(|| {
+            statement.bind(0, merchant_id)?;
+            statement.bind(1, connector_transaction_id)?;
+            statement.bind(2, payment_id)?;
+            statement.bind(3, attempt_id)?;
+            Ok(())
+        })?;
+
+        Ok((query, statement))
+    }
+}
+
+#[async_trait]
+impl PaymentAttemptStore for Cassandra {
+    fn with_consistency(self: Arc<Self>, overrides: Overrides) -> Arc<dyn PaymentAttemptStore> {
+        Arc::new(Cassandra::with_consistency(&self, overrides))
+    }
+
+    /// Writes the attempt along with its lookup table entries.
+    async fn insert(
+        &self,
+        payment_attempt: PaymentAttempt,
+    ) -> Result<PaymentAttempt, Box<dyn std::error::Error>> {
+        let mut statement = query::INSERT.statement(self)?;
+
+        query::INSERT.bind(|| payment_attempt.populate_statement(&mut statement, self.layout))?;
+
+        query::INSERT.execute(self, statement).await?;
+
+        let lookups = self.lookup_statements(&payment_attempt)?;
+
+        for (query, statement) in lookups {
+            query.execute(self, statement).await?;
+        }
+
+        Ok(payment_attempt)
+    }
+
+    /// Inserts the attempts one after the other, lookup entries included. `/create/batch` sends
+    /// them in `BATCH` statements instead, see `batch::insert`.
+    async fn insert_many(
+        &self,
+        payment_attempts: &[PaymentAttempt],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for payment_attempt in payment_attempts {
+            self.insert(payment_attempt.clone()).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn get(
+        &self,
+        payment_id: &str,
+        attempt_id: &str,
+    ) -> Result<Option<PaymentAttempt>, Box<dyn std::error::Error>> {
+        let mut statement = query::SELECT.statement(self)?;
+
+        query::SELECT.bind(|| {
+            statement.bind(0, payment_id)?;
+            statement.bind(1, attempt_id)?;
+            Ok(())
+        })?;
+
+        let rows = query::SELECT.execute(self, statement).await?;
+
+        let mut rows = rows.iter();
+
+        rows.next()
+            .map(|row| PaymentAttempt::from_row(&row, self.layout))
+            .transpose()
+    }
+
+    async fn list(
+        &self,
+        payment_id: &str,
+    ) -> Result<Vec<PaymentAttempt>, Box<dyn std::error::Error>> {
+        let mut attempts = Vec::new();
+        let mut paging_state: Option<Vec<u8>> = None;
+
+        loop {
+            let mut statement = query::LIST.statement(self)?;
+
+            query::LIST.bind(|| {
+                statement.bind(0, payment_id)?;
+
+                if let Some(paging_state) = &paging_state {
+                    statement.set_paging_state_token(paging_state)?;
+                }
+
+                Ok(())
+            })?;
+
+            let rows = query::LIST.execute(self, statement).await?;
+
+            let mut iter = rows.iter();
+            while let Some(row) = iter.next() {
+                attempts.push(PaymentAttempt::from_row(&row, self.layout)?);
+            }
+
+            paging_state = match rows.has_more_pages() {
+                true => rows.paging_state_token()?,
+                false => None,
+            };
+
+            if paging_state.is_none() {
+                break;
+            }
+        }
+
+        Ok(attempts)
+    }
+
+    async fn attempt_ids(
+        &self,
+        payment_id: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut attempt_ids = Vec::new();
+        let mut paging_state: Option<Vec<u8>> = None;
+
+        loop {
+            let mut statement = query::SELECT_ATTEMPT_IDS.statement(self)?;
+
+            query::SELECT_ATTEMPT_IDS.bind(|| {
+                statement.bind(0, payment_id)?;
+
+                if let Some(paging_state) = &paging_state {
+                    statement.set_paging_state_token(paging_state)?;
+                }
+
+                Ok(())
+            })?;
+
+            let rows = query::SELECT_ATTEMPT_IDS.execute(self, statement).await?;
+
+            let mut iter = rows.iter();
+            while let Some(row) = iter.next() {
+                let attempt_id: String = row.get_by_name("attempt_id")?;
+                attempt_ids.push(attempt_id);
+            }
+
+            paging_state = match rows.has_more_pages() {
+                true => rows.paging_state_token()?,
+                false => None,
+            };
+
+            if paging_state.is_none() {
+                break;
+            }
+        }
+
+        Ok(attempt_ids)
+    }
+
+    /// Resolves the attempts indexed under `(merchant_id, payment_id)` through point reads on
+    /// the main table.
+    async fn list_by_merchant(
+        &self,
+        merchant_id: &str,
+        payment_id: &str,
+    ) -> Result<Vec<PaymentAttempt>, Box<dyn std::error::Error>> {
+        let mut statement = query::SELECT_BY_MERCHANT.statement(self)?;
+
+        query::SELECT_BY_MERCHANT.bind(|| {
+            statement.bind(0, merchant_id)?;
+            statement.bind(1, payment_id)?;
+            Ok(())
+        })?;
+
+        let rows = query::SELECT_BY_MERCHANT.execute(self, statement).await?;
+
+        let mut attempt_ids = Vec::new();
+        let mut iter = rows.iter();
+        while let Some(row) = iter.next() {
+            let attempt_id: String = row.get_by_name("attempt_id")?;
+            attempt_ids.push(attempt_id);
+        }
+
+        let mut attempts = Vec::new();
+        for attempt_id in attempt_ids {
+            if let Some(attempt) = self.get(payment_id, &attempt_id).await? {
+                attempts.push(attempt);
+            }
+        }
+
+        Ok(attempts)
+    }
+
+    /// Resolves the attempts indexed under `(merchant_id, connector_transaction_id)` through
+    /// point reads on the main table. Index entries left behind by an attempt whose connector
+    /// transaction id has since changed are skipped.
+    async fn list_by_connector_transaction(
+        &self,
+        merchant_id: &str,
+        connector_transaction_id: &str,
+    ) -> Result<Vec<PaymentAttempt>, Box<dyn std::error::Error>> {
+        let query = query::SELECT_BY_CONNECTOR_TRANSACTION;
+        let mut statement = query.statement(self)?;
+
+        query.bind(|| {
+            statement.bind(0, merchant_id)?;
+            statement.bind(1, connector_transaction_id)?;
+            Ok(())
+        })?;
+
+        let rows = query.execute(self, statement).await?;
+
+        let mut keys = Vec::new();
+        let mut iter = rows.iter();
+        while let Some(row) = iter.next() {
+            let payment_id: String = row.get_by_name("payment_id")?;
+            let attempt_id: String = row.get_by_name("attempt_id")?;
+            keys.push((payment_id, attempt_id));
+        }
+
+        let mut attempts = Vec::new();
+        for (payment_id, attempt_id) in keys {
+            if let Some(attempt) = self.get(&payment_id, &attempt_id).await? {
+                if attempt.connector_transaction_id.as_deref() == Some(connector_transaction_id) {
+                    attempts.push(attempt);
+                }
+            }
+        }
+
+        Ok(attempts)
+    }
+
+    /// A plain update is an upsert, so updating a missing attempt writes a partial row unless
+    /// updates are conditional.
+    async fn update(
+        &self,
+        payment_id: &str,
+        attempt_id: &str,
+        update: PaymentAttemptUpdate,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (query, statement) = update.statement(self, payment_id, attempt_id)?;
+
+        let rows = query.execute(self, statement).await?;
+
+        if self.conditional_updates {
+            let applied: bool = rows
+                .first_row()
+                .context("No rows found")?
+                .get_by_name("[applied]")?;
+
+            if !applied {
+                return Err("No rows found".into());
+            }
+        }
+
+        if let Some(connector_transaction_id) = update.connector_transaction_id() {
+            let mut statement = query::SELECT_MERCHANT.statement(self)?;
+
+            query::SELECT_MERCHANT.bind(|| {
+                statement.bind(0, payment_id)?;
+                statement.bind(1, attempt_id)?;
+                Ok(())
+            })?;
+
+            let rows = query::SELECT_MERCHANT.execute(self, statement).await?;
+            let row = rows.first_row().context("No rows found")?;
+            let merchant_id: String = row.get_by_name("merchant_id")?;
+
+            let (query, statement) = self.connector_transaction_statement(
+                &merchant_id,
+                connector_transaction_id,
+                payment_id,
+                attempt_id,
+            )?;
+
+            query.execute(self, statement).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Lookup table entries are left in place, lookups skip attempts that no longer exist.
+    async fn delete(
+        &self,
+        payment_id: &str,
+        attempt_id: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut statement = query::DELETE.statement(self)?;
+
+        query::DELETE.bind(|| {
+            statement.bind(0, payment_id)?;
+            statement.bind(1, attempt_id)?;
+            Ok(())
+        })?;
+
+        query::DELETE.execute(self, statement).await?;
+
+        Ok(())
+    }
+
+    async fn delete_payment(&self, payment_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut statement = query::DELETE_PARTITION.statement(self)?;
+
+        query::DELETE_PARTITION.bind(|| {
+            statement.bind(0, payment_id)?;
+            Ok(())
+        })?;
+
+        query::DELETE_PARTITION.execute(self, statement).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,294 @@
+#[cfg(feature = "cassandra")]
+use std::env;
+use std::fmt;
+
+use axum::async_trait;
+use axum::extract::{FromRequestParts, Query};
+use axum::http::request::Parts;
+use axum::http::StatusCode;
+use serde::Deserialize;
+
+/// A CQL consistency level. Mirrors the driver's levels, so that requests are parsed the same way
+/// when the driver is not built in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Consistency {
+    Any,
+    One,
+    Two,
+    Three,
+    Quorum,
+    All,
+    LocalQuorum,
+    EachQuorum,
+    Serial,
+    LocalSerial,
+    LocalOne,
+}
+
+const LEVELS: [(Consistency, &str); 11] = [
+    (Consistency::Any, "ANY"),
+    (Consistency::One, "ONE"),
+    (Consistency::Two, "TWO"),
+    (Consistency::Three, "THREE"),
+    (Consistency::Quorum, "QUORUM"),
+    (Consistency::All, "ALL"),
+    (Consistency::LocalQuorum, "LOCAL_QUORUM"),
+    (Consistency::EachQuorum, "EACH_QUORUM"),
+    (Consistency::Serial, "SERIAL"),
+    (Consistency::LocalSerial, "LOCAL_SERIAL"),
+    (Consistency::LocalOne, "LOCAL_ONE"),
+];
+
+impl fmt::Display for Consistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (_, name) = LEVELS
+            .iter()
+            .find(|(level, _)| level == self)
+            .expect("Every level has a name");
+
+        f.write_str(name)
+    }
+}
+
+#[cfg(feature = "cassandra")]
+impl From<Consistency> for cassandra_cpp::Consistency {
+    fn from(consistency: Consistency) -> Self {
+        match consistency {
+            Consistency::Any => Self::ANY,
+            Consistency::One => Self::ONE,
+            Consistency::Two => Self::TWO,
+            Consistency::Three => Self::THREE,
+            Consistency::Quorum => Self::QUORUM,
+            Consistency::All => Self::ALL,
+            Consistency::LocalQuorum => Self::LOCAL_QUORUM,
+            Consistency::EachQuorum => Self::EACH_QUORUM,
+            Consistency::Serial => Self::SERIAL,
+            Consistency::LocalSerial => Self::LOCAL_SERIAL,
+            Consistency::LocalOne => Self::LOCAL_ONE,
+        }
+    }
+}
+
+/// Consistency levels statements are executed with, split by whether the statement reads or
+/// writes.
+#[cfg(feature = "cassandra")]
+#[derive(Clone, Copy)]
+pub struct Consistencies {
+    pub read: Consistency,
+    pub write: Consistency,
+    /// Only used by Cassandra for conditional statements
+    pub serial: Option<Consistency>,
+}
+
+#[cfg(feature = "cassandra")]
+impl Consistencies {
+    /// Reads `READ_CONSISTENCY`, `WRITE_CONSISTENCY` and `SERIAL_CONSISTENCY`, falling back to the
+    /// driver's default of `LOCAL_ONE`.
+    pub fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
+        let level = |name: &str| {
+            env::var(name)
+                .ok()
+                .map(|value| parse(&value).map_err(|err| format!("Invalid {name}: {err}")))
+                .transpose()
+        };
+
+        Ok(Self {
+            read: level("READ_CONSISTENCY")?.unwrap_or(Consistency::LocalOne),
+            write: level("WRITE_CONSISTENCY")?.unwrap_or(Consistency::LocalOne),
+            serial: level("SERIAL_CONSISTENCY")?
+                .map(serial_level)
+                .transpose()
+                .map_err(|err| format!("Invalid SERIAL_CONSISTENCY: {err}"))?,
+        })
+    }
+
+    /// Applies the levels a request asked for.
+    pub fn with(self, overrides: Overrides) -> Self {
+        Self {
+            read: overrides.read.unwrap_or(self.read),
+            write: overrides.write.unwrap_or(self.write),
+            serial: overrides.serial.or(self.serial),
+        }
+    }
+}
+
+/// Per-request consistency, taken from the `read_consistency`, `write_consistency` and
+/// `serial_consistency` query parameters or else the `x-read-consistency`,
+/// `x-write-consistency` and `x-serial-consistency` headers. `consistency` and `x-consistency`
+/// set both the read and the write level, below the specific ones.
+#[derive(Default)]
+pub struct Overrides {
+    read: Option<Consistency>,
+    write: Option<Consistency>,
+    serial: Option<Consistency>,
+}
+
+#[derive(Default, Deserialize)]
+struct OverrideParams {
+    consistency: Option<String>,
+    read_consistency: Option<String>,
+    write_consistency: Option<String>,
+    serial_consistency: Option<String>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Overrides {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(params) = Query::<OverrideParams>::from_request_parts(parts, state)
+            .await
+            .map_err(|err| (StatusCode::BAD_REQUEST, err.to_string()))?;
+
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .map(|value| value.to_str().map(str::to_string))
+                .transpose()
+                .map_err(|err| format!("Invalid {name}: {err}"))
+        };
+
+        Self::resolve(params, header).map_err(|err| (StatusCode::BAD_REQUEST, err))
+    }
+}
+
+impl Overrides {
+    /// Whether the request left every level to the configuration.
+    pub fn is_empty(&self) -> bool {
+        self.read.is_none() && self.write.is_none() && self.serial.is_none()
+    }
+
+    /// Picks every level from its query parameter or else its header.
+    fn resolve(
+        params: OverrideParams,
+        header: impl Fn(&str) -> Result<Option<String>, String>,
+    ) -> Result<Self, String> {
+        let level = |param: Option<String>, name: &str| match param {
+            Some(value) => Ok(Some(value)),
+            None => header(name),
+        };
+
+        let shared = level(params.consistency, "x-consistency")?;
+        let read = level(params.read_consistency, "x-read-consistency")?.or(shared.clone());
+        let write = level(params.write_consistency, "x-write-consistency")?.or(shared);
+        let serial = level(params.serial_consistency, "x-serial-consistency")?;
+
+        Ok(Self {
+            read: read.map(|value| parse(&value)).transpose()?,
+            write: write.map(|value| parse(&value)).transpose()?,
+            serial: serial
+                .map(|value| parse(&value).and_then(serial_level))
+                .transpose()?,
+        })
+    }
+}
+
+/// Parses a level by its CQL name, such as `LOCAL_QUORUM`, ignoring case.
+fn parse(value: &str) -> Result<Consistency, String> {
+    LEVELS
+        .iter()
+        .find(|(_, name)| name.eq_ignore_ascii_case(value))
+        .map(|(level, _)| *level)
+        .ok_or_else(|| format!("Unrecognized Consistency: {value}"))
+}
+
+fn serial_level(consistency: Consistency) -> Result<Consistency, String> {
+    match consistency {
+        Consistency::Serial | Consistency::LocalSerial => Ok(consistency),
+        _ => Err(format!(
+            "{consistency} is not a serial consistency, expected SERIAL or LOCAL_SERIAL"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn resolve(params: OverrideParams, headers: &[(&str, &str)]) -> Result<Overrides, String> {
+        let headers = headers.iter().copied().collect::<HashMap<_, _>>();
+        Overrides::resolve(params, |name| {
+            Ok(headers.get(name).map(|value| value.to_string()))
+        })
+    }
+
+    #[test]
+    fn levels_parse_by_cql_name_ignoring_case() {
+        assert_eq!(parse("local_quorum"), Ok(Consistency::LocalQuorum));
+        assert_eq!(parse("ALL"), Ok(Consistency::All));
+        assert!(parse("unknown").is_err());
+        assert!(parse("most").is_err());
+
+        assert_eq!(
+            serial_level(Consistency::LocalSerial),
+            Ok(Consistency::LocalSerial)
+        );
+        assert!(serial_level(Consistency::Quorum).is_err());
+    }
+
+    #[test]
+    fn shared_level_covers_reads_and_writes() {
+        let overrides = resolve(OverrideParams::default(), &[("x-consistency", "quorum")]).unwrap();
+
+        assert_eq!(overrides.read, Some(Consistency::Quorum));
+        assert_eq!(overrides.write, Some(Consistency::Quorum));
+        assert_eq!(overrides.serial, None);
+    }
+
+    #[test]
+    fn specific_levels_win_over_the_shared_one() {
+        let params = OverrideParams {
+            consistency: Some("one".to_string()),
+            write_consistency: Some("all".to_string()),
+            ..Default::default()
+        };
+        let overrides = resolve(params, &[("x-read-consistency", "local_quorum")]).unwrap();
+
+        assert_eq!(overrides.read, Some(Consistency::LocalQuorum));
+        assert_eq!(overrides.write, Some(Consistency::All));
+    }
+
+    #[test]
+    fn query_parameters_win_over_headers() {
+        let params = OverrideParams {
+            serial_consistency: Some("local_serial".to_string()),
+            ..Default::default()
+        };
+        let overrides = resolve(params, &[("x-serial-consistency", "serial")]).unwrap();
+
+        assert_eq!(overrides.serial, Some(Consistency::LocalSerial));
+    }
+
+    #[test]
+    fn invalid_levels_are_rejected() {
+        assert!(resolve(OverrideParams::default(), &[("x-consistency", "most")]).is_err());
+        assert!(resolve(
+            OverrideParams::default(),
+            &[("x-serial-consistency", "quorum")]
+        )
+        .is_err());
+    }
+
+    #[cfg(feature = "cassandra")]
+    #[test]
+    fn overrides_replace_configured_levels() {
+        let configured = Consistencies {
+            read: Consistency::LocalOne,
+            write: Consistency::LocalOne,
+            serial: None,
+        };
+        let overrides = Overrides {
+            write: Some(Consistency::Quorum),
+            serial: Some(Consistency::Serial),
+            ..Default::default()
+        };
+
+        let consistencies = configured.with(overrides);
+        assert_eq!(consistencies.read, Consistency::LocalOne);
+        assert_eq!(consistencies.write, Consistency::Quorum);
+        assert_eq!(consistencies.serial, Some(Consistency::Serial));
+    }
+}
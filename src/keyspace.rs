@@ -0,0 +1,247 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::str::FromStr;
+
+use anyhow::Context;
+
+/// Keyspace every table lives in, and how it is replicated.
+pub struct Keyspace {
+    name: String,
+    /// Only set when `KEYSPACE_REPLICATION` is, an existing keyspace is altered to match
+    replication: Option<Replication>,
+    /// Only set when `KEYSPACE_DURABLE_WRITES` is, an existing keyspace is altered to match
+    durable_writes: Option<bool>,
+}
+
+/// Replication of the keyspace, parsed from `KEYSPACE_REPLICATION`.
+pub enum Replication {
+    /// `SimpleStrategy` with a single replication factor, given as a bare number such as `3`
+    Simple(u32),
+    /// `NetworkTopologyStrategy` with a factor per datacenter, given as `dc1:3,dc2:3`
+    NetworkTopology(BTreeMap<String, u32>),
+}
+
+impl FromStr for Replication {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if !value.contains(':') {
+            return Ok(Self::Simple(value.trim().parse()?));
+        }
+
+        let datacenters = value
+            .split(',')
+            .map(|datacenter| {
+                let (name, factor) = datacenter
+                    .split_once(':')
+                    .ok_or_else(|| format!("Expected <datacenter>:<factor>, got {datacenter}"))?;
+                let name = name.trim();
+
+                if name.is_empty() || name.contains('\'') {
+                    return Err(format!("Invalid datacenter name: {name}").into());
+                }
+
+                Ok((name.to_string(), factor.trim().parse()?))
+            })
+            .collect::<Result<_, Box<dyn std::error::Error>>>()?;
+
+        Ok(Self::NetworkTopology(datacenters))
+    }
+}
+
+impl Replication {
+    /// The `REPLICATION` map of `CREATE KEYSPACE`.
+    fn cql(&self) -> String {
+        match self {
+            Self::Simple(factor) => {
+                format!("{{ 'class' : 'SimpleStrategy', 'replication_factor' : {factor} }}")
+            }
+            Self::NetworkTopology(datacenters) => {
+                let factors = datacenters
+                    .iter()
+                    .map(|(name, factor)| format!(", '{name}' : {factor}"))
+                    .collect::<String>();
+
+                format!("{{ 'class' : 'NetworkTopologyStrategy'{factors} }}")
+            }
+        }
+    }
+}
+
+impl Keyspace {
+    /// Reads `KEYSPACE` (default `payments`), `KEYSPACE_REPLICATION` and
+    /// `KEYSPACE_DURABLE_WRITES`. A new keyspace defaults to a replication factor of `1` with
+    /// durable writes.
+    pub fn from_env() -> Result<Self, Box<dyn std::error::Error>> {
+        let name = env::var("KEYSPACE").unwrap_or("payments".to_string());
+
+        let replication = env::var("KEYSPACE_REPLICATION")
+            .ok()
+            .map(|replication| replication.parse::<Replication>())
+            .transpose()
+            .map_err(|err| format!("Invalid KEYSPACE_REPLICATION: {err}"))?;
+
+        let durable_writes = env::var("KEYSPACE_DURABLE_WRITES")
+            .ok()
+            .map(|durable_writes| durable_writes.parse::<bool>())
+            .transpose()
+            .context("Failed while parsing KEYSPACE_DURABLE_WRITES")?;
+
+        Self::new(name, replication, durable_writes)
+    }
+
+    /// Fails unless `name` is a valid, unquoted keyspace name.
+    pub fn new(
+        name: String,
+        replication: Option<Replication>,
+        durable_writes: Option<bool>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || char == '_')
+        {
+            return Err(format!("Invalid KEYSPACE: {name}").into());
+        }
+
+        Ok(Self {
+            name,
+            replication,
+            durable_writes,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Fills in `{keyspace}` with the keyspace name.
+    pub fn render(&self, cql: &str) -> String {
+        cql.replace("{keyspace}", &self.name)
+    }
+
+    /// Statements creating the keyspace, then altering it when `replication` or `durable_writes`
+    /// was configured explicitly, so that an existing keyspace keeps its settings otherwise.
+    /// Altering does not move existing data, that takes a `nodetool repair`.
+    pub fn statements(&self) -> Vec<String> {
+        let replication = self
+            .replication
+            .as_ref()
+            .unwrap_or(&Replication::Simple(1))
+            .cql();
+        let durable_writes = self.durable_writes.unwrap_or(true).to_string();
+        let mut statements = vec![self
+            .render(
+                &include_str!("keyspace.cql")
+                    .replace("{replication}", &replication)
+                    .replace("{durable_writes}", &durable_writes),
+            )
+            .trim()
+            .trim_end_matches(';')
+            .to_string()];
+
+        let options = [
+            self.replication
+                .as_ref()
+                .map(|replication| format!("REPLICATION = {}", replication.cql())),
+            self.durable_writes
+                .map(|durable_writes| format!("DURABLE_WRITES = {durable_writes}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+        if !options.is_empty() {
+            statements.push(self.render(&format!(
+                "ALTER KEYSPACE {{keyspace}} WITH {}",
+                options.join(" AND ")
+            )));
+        }
+
+        statements
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyspace(replication: Option<&str>, durable_writes: Option<bool>) -> Keyspace {
+        let replication = replication.map(|replication| replication.parse().unwrap());
+
+        Keyspace::new("payments".to_string(), replication, durable_writes).unwrap()
+    }
+
+    #[test]
+    fn parses_simple_replication() {
+        let replication: Replication = " 3 ".parse().unwrap();
+
+        assert_eq!(
+            replication.cql(),
+            "{ 'class' : 'SimpleStrategy', 'replication_factor' : 3 }"
+        );
+    }
+
+    #[test]
+    fn parses_network_topology_replication() {
+        let replication: Replication = "dc2:2, dc1 : 3".parse().unwrap();
+
+        assert_eq!(
+            replication.cql(),
+            "{ 'class' : 'NetworkTopologyStrategy', 'dc1' : 3, 'dc2' : 2 }"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_replication() {
+        for value in ["", "three", "dc1", "dc1:3,dc2", ":3", "dc'1:3", "dc1:-1"] {
+            assert!(value.parse::<Replication>().is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        for name in ["", "pay-ments", "payments;", "\"payments\""] {
+            assert!(
+                Keyspace::new(name.to_string(), None, None).is_err(),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn creates_without_altering_by_default() {
+        assert_eq!(
+            keyspace(None, None).statements(),
+            ["CREATE KEYSPACE IF NOT EXISTS payments\n\
+              WITH REPLICATION = { 'class' : 'SimpleStrategy', 'replication_factor' : 1 }\n\
+              AND DURABLE_WRITES = true"]
+        );
+    }
+
+    #[test]
+    fn alters_only_the_configured_options() {
+        let statements = keyspace(Some("3"), None).statements();
+        assert_eq!(
+            statements[1..],
+            ["ALTER KEYSPACE payments WITH \
+              REPLICATION = { 'class' : 'SimpleStrategy', 'replication_factor' : 3 }"]
+        );
+
+        let statements = keyspace(None, Some(false)).statements();
+        assert_eq!(
+            statements[1..],
+            ["ALTER KEYSPACE payments WITH DURABLE_WRITES = false"]
+        );
+
+        let statements = keyspace(Some("dc1:3"), Some(false)).statements();
+        assert!(statements[0].contains("'dc1' : 3"));
+        assert!(statements[0].contains("DURABLE_WRITES = false"));
+        assert_eq!(
+            statements[1..],
+            ["ALTER KEYSPACE payments WITH \
+              REPLICATION = { 'class' : 'NetworkTopologyStrategy', 'dc1' : 3 } \
+              AND DURABLE_WRITES = false"]
+        );
+    }
+}
@@ -0,0 +1,254 @@
+use std::borrow::Cow;
+use std::str::FromStr;
+
+#[cfg(feature = "cassandra")]
+use cassandra_cpp::{AsRustType, BindRustType, Row, Statement, UserType};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use time::{OffsetDateTime, PrimitiveDateTime};
+
+#[cfg(feature = "cassandra")]
+use crate::keyspace::Keyspace;
+#[cfg(feature = "cassandra")]
+use crate::user_types::UserDefined;
+
+/// How attempts are stored in Cassandra. Both layouts live in their own table, so they can be
+/// benchmarked side by side against the same keyspace.
+#[derive(Clone, Copy, Default)]
+pub enum Layout {
+    /// Dates, enums and nested data as serde_json encoded `text`, in `payment_attempts`
+    #[default]
+    Text,
+    /// Dates as `timestamp`, enums by variant name and mandate data as user-defined types, in
+    /// `payment_attempts_native`. Timestamps are truncated to milliseconds.
+    Native,
+}
+
+impl FromStr for Layout {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(Self::Text),
+            "native" => Ok(Self::Native),
+            _ => Err(format!("Unknown LAYOUT: {value}").into()),
+        }
+    }
+}
+
+impl Layout {
+    /// Unit enum, as a JSON string or by its bare variant name.
+    pub fn enum_column<T: Serialize>(
+        self,
+        value: &T,
+    ) -> Result<Column<'static>, Box<dyn std::error::Error>> {
+        match self {
+            Self::Text => Ok(serde_json::to_string(value)?.into()),
+            Self::Native => Ok(variant_name(value)?.into()),
+        }
+    }
+
+    /// Date, as a JSON string or as milliseconds since the epoch for a `timestamp` column.
+    pub fn time_column(
+        self,
+        value: &PrimitiveDateTime,
+    ) -> Result<Column<'static>, Box<dyn std::error::Error>> {
+        match self {
+            Self::Text => Ok(serde_json::to_string(value)?.into()),
+            Self::Native => Ok(Column::Timestamp(millis(value)?)),
+        }
+    }
+}
+
+/// Reading and writing rows, only with the driver built in.
+#[cfg(feature = "cassandra")]
+impl Layout {
+    pub fn table(self) -> &'static str {
+        match self {
+            Self::Text => "payment_attempts",
+            Self::Native => "payment_attempts_native",
+        }
+    }
+
+    /// Points a query at this layout's table in `keyspace` by filling in `{payment_attempts}`
+    /// and `{keyspace}`.
+    pub fn render(self, cql: &str, keyspace: &Keyspace) -> String {
+        keyspace.render(&cql.replace(
+            "{payment_attempts}",
+            &format!("{{keyspace}}.{}", self.table()),
+        ))
+    }
+
+    pub fn get_enum<T: DeserializeOwned>(
+        self,
+        row: &impl Record,
+        name: &str,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let data = row.text(name)?;
+
+        match self {
+            Self::Text => Ok(serde_json::from_str(&data)?),
+            Self::Native => from_variant_name(data),
+        }
+    }
+
+    pub fn get_time(
+        self,
+        row: &impl Record,
+        name: &str,
+    ) -> Result<PrimitiveDateTime, Box<dyn std::error::Error>> {
+        match self {
+            Self::Text => Ok(serde_json::from_str(&row.text(name)?)?),
+            Self::Native => from_millis(row.big_int(name)?),
+        }
+    }
+
+    /// Nested data such as the mandate types, as JSON text or as a user-defined type.
+    pub fn nested_column<T: UserDefined>(
+        self,
+        value: &T,
+    ) -> Result<Column<'static>, Box<dyn std::error::Error>> {
+        match self {
+            Self::Text => Ok(serde_json::to_string(value)?.into()),
+            Self::Native => Ok(Column::UserType(value.to_user_type()?)),
+        }
+    }
+
+    pub fn get_nested<T: UserDefined>(
+        self,
+        row: &impl Record,
+        name: &str,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        match self {
+            Self::Text => Ok(serde_json::from_str(&row.text(name)?)?),
+            Self::Native => row.user_type(name),
+        }
+    }
+}
+
+/// Columns of a stored row, read by name. Decoding goes through this rather than the driver's
+/// `Row` so that it can be checked against encoded columns without a cluster.
+#[cfg(feature = "cassandra")]
+pub trait Record {
+    fn is_null(&self, name: &str) -> Result<bool, Box<dyn std::error::Error>>;
+
+    fn text(&self, name: &str) -> Result<String, Box<dyn std::error::Error>>;
+
+    /// A `bigint`, or a `timestamp` as milliseconds since the epoch
+    fn big_int(&self, name: &str) -> Result<i64, Box<dyn std::error::Error>>;
+
+    fn small_int(&self, name: &str) -> Result<i16, Box<dyn std::error::Error>>;
+
+    fn boolean(&self, name: &str) -> Result<bool, Box<dyn std::error::Error>>;
+
+    fn user_type<T: UserDefined>(&self, name: &str) -> Result<T, Box<dyn std::error::Error>>;
+}
+
+#[cfg(feature = "cassandra")]
+impl Record for Row<'_> {
+    fn is_null(&self, name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(self.get_column_by_name(name)?.is_null())
+    }
+
+    fn text(&self, name: &str) -> Result<String, Box<dyn std::error::Error>> {
+        Ok(self.get_by_name(name)?)
+    }
+
+    fn big_int(&self, name: &str) -> Result<i64, Box<dyn std::error::Error>> {
+        Ok(self.get_by_name(name)?)
+    }
+
+    fn small_int(&self, name: &str) -> Result<i16, Box<dyn std::error::Error>> {
+        Ok(self.get_by_name(name)?)
+    }
+
+    fn boolean(&self, name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(self.get_by_name(name)?)
+    }
+
+    fn user_type<T: UserDefined>(&self, name: &str) -> Result<T, Box<dyn std::error::Error>> {
+        T::from_user_type(&self.get_column_by_name(name)?)
+    }
+}
+
+/// Name a unit variant is serialized under.
+pub fn variant_name<T: Serialize>(value: &T) -> Result<String, Box<dyn std::error::Error>> {
+    match serde_json::to_value(value)? {
+        serde_json::Value::String(name) => Ok(name),
+        value => Err(format!("Variant is not a unit variant: {value}").into()),
+    }
+}
+
+pub fn from_variant_name<T: DeserializeOwned>(
+    name: String,
+) -> Result<T, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_value(serde_json::Value::String(name))?)
+}
+
+/// Milliseconds since the epoch, as stored in a `timestamp`.
+pub fn millis(value: &PrimitiveDateTime) -> Result<i64, Box<dyn std::error::Error>> {
+    let millis = value.assume_utc().unix_timestamp_nanos() / 1_000_000;
+    Ok(i64::try_from(millis)?)
+}
+
+pub fn from_millis(millis: i64) -> Result<PrimitiveDateTime, Box<dyn std::error::Error>> {
+    let date_time = OffsetDateTime::from_unix_timestamp_nanos(i128::from(millis) * 1_000_000)?;
+    Ok(PrimitiveDateTime::new(date_time.date(), date_time.time()))
+}
+
+/// A value encoded for its column, ready to be bound. Text borrows from the attempt when it is
+/// stored as is.
+pub enum Column<'a> {
+    Text(Cow<'a, str>),
+    BigInt(i64),
+    SmallInt(i16),
+    Boolean(bool),
+    Timestamp(i64),
+    #[cfg(feature = "cassandra")]
+    UserType(UserType),
+}
+
+#[cfg(feature = "cassandra")]
+impl Column<'_> {
+    pub fn bind(self, stmt: &mut Statement, loc: usize) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            Self::Text(value) => stmt.bind(loc, value.as_ref())?,
+            Self::BigInt(value) | Self::Timestamp(value) => stmt.bind(loc, value)?,
+            Self::SmallInt(value) => stmt.bind(loc, value)?,
+            Self::Boolean(value) => stmt.bind(loc, value)?,
+            Self::UserType(value) => stmt.bind(loc, &value)?,
+        };
+
+        Ok(())
+    }
+}
+
+impl From<String> for Column<'_> {
+    fn from(value: String) -> Self {
+        Self::Text(Cow::Owned(value))
+    }
+}
+
+impl<'a> From<&'a str> for Column<'a> {
+    fn from(value: &'a str) -> Self {
+        Self::Text(Cow::Borrowed(value))
+    }
+}
+
+impl From<i64> for Column<'_> {
+    fn from(value: i64) -> Self {
+        Self::BigInt(value)
+    }
+}
+
+impl From<i16> for Column<'_> {
+    fn from(value: i16) -> Self {
+        Self::SmallInt(value)
+    }
+}
+
+impl From<bool> for Column<'_> {
+    fn from(value: bool) -> Self {
+        Self::Boolean(value)
+    }
+}
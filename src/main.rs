@@ -1,44 +1,250 @@
 use anyhow::{Context, Result};
-use axum::extract::{Path, State};
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::routing::{get, post};
+use axum::routing::{delete, get, patch, post};
+use axum::Json;
+use rand::seq::SliceRandom;
 use serde::{Deserialize, Serialize};
 use time::PrimitiveDateTime;
 use tokio::net::TcpListener;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
 
+#[cfg(feature = "cassandra")]
+use self::cassandra::Cassandra;
+#[cfg(feature = "cassandra")]
+use self::consistency::Consistencies;
+use self::consistency::Overrides;
+#[cfg(feature = "cassandra")]
+use self::keyspace::Keyspace;
+#[cfg(feature = "cassandra")]
+use self::layout::{Column, Layout, Record};
+use self::memory::MemoryStore;
+use self::metrics::Metrics;
+use self::postgres::Postgres;
 use self::randr::Randr;
+use self::stats::{Operation, Stats};
+use self::store::PaymentAttemptStore;
+use self::update::PaymentAttemptUpdate;
 
+#[cfg(feature = "cassandra")]
+mod batch;
+mod bench;
+#[cfg(feature = "cassandra")]
+mod cassandra;
+mod consistency;
+#[cfg(feature = "cassandra")]
+mod keyspace;
+mod layout;
+mod memory;
+mod metrics;
+#[cfg(feature = "cassandra")]
+mod migrate;
+mod payloads;
+mod postgres;
+#[cfg(feature = "cassandra")]
+mod query;
 mod randr;
+mod stats;
 mod storage_enums;
+mod store;
+mod update;
+#[cfg(feature = "cassandra")]
+mod user_types;
 
+#[cfg(feature = "cassandra")]
 use cassandra_cpp::*;
 use std::env;
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let cas_url = env::var("CASSANDRA_URL").context("CASSANDRA_URL not found")?;
-    let cas_username = env::var("CASSANDRA_USERNAME").context("CASSANDRA_USERNAME not found")?;
-    let cas_password = env::var("CASSANDRA_PASSWORD").context("CASSANDRA_PASSWORD not found")?;
+#[derive(Clone)]
+struct AppState {
+    store: Arc<dyn PaymentAttemptStore>,
+    /// Backs the routes that are specific to Cassandra, such as batches and driver metrics.
+    /// `None` unless running with `STORAGE=cassandra`.
+    #[cfg(feature = "cassandra")]
+    cassandra: Option<Cassandra>,
+    /// Shared so that a seeded run hands out one reproducible sequence across requests
+    randr: Arc<Mutex<randr::Context>>,
+    generator: Generator,
+    stats: Arc<Stats>,
+    metrics: Arc<Metrics>,
+}
 
-    let mut cluster = Cluster::default();
+impl AppState {
+    /// Runs a store call, timing it under `operation` and counting its errors.
+    async fn instrument<T>(
+        &self,
+        operation: Operation,
+        future: impl Future<Output = Result<T, Box<dyn std::error::Error>>>,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        let _in_flight = self.metrics.store_started();
+        let output = self.stats.time(operation, future).await;
 
-    cluster
-        .set_contact_points(&cas_url)?
-        .set_credentials(&cas_username, &cas_password)?
-        .set_load_balance_round_robin();
+        if let Err(err) = &output {
+            self.metrics.store_failed(err.as_ref());
+        }
+
+        output
+    }
+
+    #[cfg(feature = "cassandra")]
+    fn cassandra(&self) -> Result<&Cassandra, String> {
+        self.cassandra
+            .as_ref()
+            .ok_or("Only available with STORAGE=cassandra".to_string())
+    }
+}
+
+/// How `/create` builds an attempt when no body is sent.
+#[derive(Clone, Copy, Default)]
+enum Generator {
+    /// Every field is generated independently
+    #[default]
+    Random,
+    /// Fields are generated so that they respect payment invariants
+    Consistent,
+}
+
+impl Generator {
+    fn generate(self, ctx: &mut randr::Context) -> PaymentAttempt {
+        match self {
+            Self::Random => PaymentAttempt::randr(ctx, None, None),
+            Self::Consistent => PaymentAttempt::randr(ctx, Some(PaymentAttempt::consistent), None),
+        }
+    }
+}
+
+/// Where attempts are stored, picked with `STORAGE`. Defaults to Cassandra when the driver is
+/// built in, and to the in-process store otherwise.
+#[derive(Clone, Copy, Default)]
+enum Storage {
+    #[cfg(feature = "cassandra")]
+    #[default]
+    Cassandra,
+    /// In-process store, no Cassandra or credentials are needed
+    #[cfg_attr(not(feature = "cassandra"), default)]
+    Memory,
+    /// Postgres at `POSTGRES_URL`
+    Postgres,
+}
+
+impl FromStr for Storage {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            #[cfg(feature = "cassandra")]
+            "cassandra" => Ok(Self::Cassandra),
+            #[cfg(not(feature = "cassandra"))]
+            "cassandra" => Err("STORAGE=cassandra needs the cassandra feature".into()),
+            "memory" => Ok(Self::Memory),
+            "postgres" => Ok(Self::Postgres),
+            _ => Err(format!("Unknown STORAGE: {value}").into()),
+        }
+    }
+}
+
+impl Storage {
+    fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "cassandra")]
+            Self::Cassandra => "cassandra",
+            Self::Memory => "memory",
+            Self::Postgres => "postgres",
+        }
+    }
+}
+
+impl FromStr for Generator {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "random" => Ok(Self::Random),
+            "consistent" => Ok(Self::Consistent),
+            _ => Err(format!("Unknown GENERATOR: {value}").into()),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    init_tracing()?;
 
-    let session = cluster.connect().await?;
+    match env::args().nth(1).as_deref() {
+        Some("bench") => return bench::run().await,
+        #[cfg(feature = "cassandra")]
+        Some("migrate") => {
+            let report = migrate::run(&connect().await?, &Keyspace::from_env()?).await?;
+            println!("{}", serde_json::to_string(&report)?);
+            return Ok(());
+        }
+        _ => {}
+    }
 
     let server_host = env::var("SERVER_HOST").unwrap_or("localhost".to_string());
     let server_port = env::var("SERVER_PORT").unwrap_or("8000".to_string());
 
-    create_table(&session).await?;
+    let storage = storage()?;
+
+    #[cfg(feature = "cassandra")]
+    let cassandra = match storage {
+        Storage::Cassandra => Some(cassandra().await?),
+        Storage::Memory | Storage::Postgres => None,
+    };
+
+    let store: Arc<dyn PaymentAttemptStore> = match storage {
+        #[cfg(feature = "cassandra")]
+        Storage::Cassandra => Arc::new(cassandra.clone().context("Not connected to Cassandra")?),
+        Storage::Memory => Arc::new(MemoryStore::default()),
+        Storage::Postgres => Arc::new(postgres().await?),
+    };
+
+    let state = AppState {
+        store,
+        #[cfg(feature = "cassandra")]
+        cassandra,
+        randr: Arc::new(Mutex::new(randr_context()?)),
+        generator: generator()?,
+        stats: Arc::new(Stats::new()),
+        metrics: Arc::new(Metrics::new(storage.name())?),
+    };
+
+    let track = axum::middleware::from_fn_with_state(state.metrics.clone(), metrics::track);
 
-    let router: axum::Router<()> = axum::Router::new()
+    let router = axum::Router::new()
         .route("/create", post(add_entry))
-        .route("/retrieve", get(retrieve_entry))
-        .route("/create-table", post(fun))
-        .with_state(session)
+        .route("/create/batch", post(add_batch_entries))
+        .route("/retrieve/:payment_id/:attempt_id", get(retrieve_entry))
+        .route("/payments/:payment_id/attempts", get(list_entries))
+        .route(
+            "/merchants/:merchant_id/payments/:payment_id/attempts",
+            get(list_merchant_entries),
+        )
+        .route(
+            "/merchants/:merchant_id/connector-transactions/:connector_transaction_id",
+            get(list_connector_transaction_entries),
+        )
+        .route(
+            "/attempts/:payment_id/:attempt_id",
+            patch(update_entry).delete(delete_entry),
+        )
+        .route("/payments/:payment_id", delete(delete_partition_entries))
+        .route("/stats", get(stats))
+        .route("/stats/reset", post(reset_stats))
+        .route("/metrics", get(metrics));
+
+    #[cfg(feature = "cassandra")]
+    let router = router.route("/create-table", post(fun));
+
+    let router = router
+        .route_layer(track)
+        .with_state(state)
         .route("/health", get(|| async { "OK" }));
 
     let server = axum::serve(
@@ -57,17 +263,226 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-async fn fun(State(state): State<Session>) -> Result<impl IntoResponse, String> {
-    create_table(&state).await.map_err(|err| err.to_string())?;
+/// Logs to stdout as pretty printed or JSON lines (`LOG_FORMAT=pretty|json`), filtered by
+/// `RUST_LOG`. Every span reports its busy and idle time when it closes.
+fn init_tracing() -> Result<(), Box<dyn std::error::Error>> {
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .with_span_events(FmtSpan::CLOSE);
+
+    match env::var("LOG_FORMAT").as_deref() {
+        Ok("pretty") | Err(_) => subscriber.pretty().init(),
+        Ok("json") => subscriber.json().init(),
+        Ok(format) => return Err(format!("Unknown LOG_FORMAT: {format}").into()),
+    }
 
-    Ok("Table Created".to_string())
+    Ok(())
 }
 
-async fn add_entry(State(state): State<Session>) -> Result<impl IntoResponse, String> {
-    let start = tokio::time::Instant::now();
-    let output = add_data(&state).await;
-    let duration = start.elapsed();
-    println!("[INFO] Add Entry: {}", duration.as_micros());
+/// Connects, applies pending migrations to the configured keyspace (or checks that there are
+/// none with `MIGRATE=false`) and prepares the queries for the configured `LAYOUT`.
+#[cfg(feature = "cassandra")]
+async fn cassandra() -> Result<Cassandra, Box<dyn std::error::Error>> {
+    let session = connect().await?;
+    let keyspace = Keyspace::from_env()?;
+
+    if migrate()? {
+        migrate::run(&session, &keyspace).await?;
+    } else {
+        migrate::verify(&session, &keyspace).await?;
+    }
+
+    Cassandra::new(
+        session,
+        layout()?,
+        keyspace,
+        prepared()?,
+        Consistencies::from_env()?,
+        conditional_updates()?,
+    )
+    .await
+}
+
+/// Connects to `POSTGRES_URL` with a pool of `POSTGRES_POOL_SIZE` connections (default 16).
+async fn postgres() -> Result<Postgres, Box<dyn std::error::Error>> {
+    let url = env::var("POSTGRES_URL").context("POSTGRES_URL not found")?;
+    let pool_size = env::var("POSTGRES_POOL_SIZE")
+        .ok()
+        .map(|pool_size| pool_size.parse::<usize>())
+        .transpose()
+        .context("Failed while parsing POSTGRES_POOL_SIZE")?
+        .unwrap_or(16);
+
+    Postgres::connect(&url, pool_size).await
+}
+
+#[cfg(feature = "cassandra")]
+async fn connect() -> Result<Session, Box<dyn std::error::Error>> {
+    let cas_url = env::var("CASSANDRA_URL").context("CASSANDRA_URL not found")?;
+    let cas_username = env::var("CASSANDRA_USERNAME").context("CASSANDRA_USERNAME not found")?;
+    let cas_password = env::var("CASSANDRA_PASSWORD").context("CASSANDRA_PASSWORD not found")?;
+
+    let mut cluster = Cluster::default();
+
+    cluster
+        .set_contact_points(&cas_url)?
+        .set_credentials(&cas_username, &cas_password)?
+        .set_load_balance_round_robin();
+
+    Ok(cluster.connect().await?)
+}
+
+fn randr_context() -> Result<randr::Context, Box<dyn std::error::Error>> {
+    let seed = env::var("SEED")
+        .ok()
+        .map(|seed| seed.parse::<u64>())
+        .transpose()
+        .context("Failed while parsing SEED")?;
+
+    let mut ctx = randr::Context::new(seed);
+
+    if let Ok(weights) = env::var("RANDR_WEIGHTS") {
+        storage_enums::apply_weights(&mut ctx, &weights)?;
+    }
+
+    if let Ok(payload_bytes) = env::var("PAYLOAD_BYTES") {
+        ctx.set_payload_bytes(
+            payload_bytes
+                .parse()
+                .context("Failed while parsing PAYLOAD_BYTES")?,
+        );
+    }
+
+    Ok(ctx)
+}
+
+/// Whether queries are prepared at startup (`PREPARED`, on by default).
+#[cfg(feature = "cassandra")]
+fn prepared() -> Result<bool, Box<dyn std::error::Error>> {
+    let prepared = env::var("PREPARED")
+        .ok()
+        .map(|prepared| prepared.parse::<bool>())
+        .transpose()
+        .context("Failed while parsing PREPARED")?
+        .unwrap_or(true);
+
+    Ok(prepared)
+}
+
+/// Whether Cassandra updates only apply to existing attempts (`CONDITIONAL_UPDATES`, off by
+/// default). Conditional updates are lightweight transactions, mixing them with the plain
+/// inserts and deletes on the same rows is unsafe, so this is for testing the contract only.
+#[cfg(feature = "cassandra")]
+fn conditional_updates() -> Result<bool, Box<dyn std::error::Error>> {
+    let conditional_updates = env::var("CONDITIONAL_UPDATES")
+        .ok()
+        .map(|conditional_updates| conditional_updates.parse::<bool>())
+        .transpose()
+        .context("Failed while parsing CONDITIONAL_UPDATES")?
+        .unwrap_or(false);
+
+    Ok(conditional_updates)
+}
+
+/// Whether pending migrations are applied at startup (`MIGRATE`, on by default).
+#[cfg(feature = "cassandra")]
+fn migrate() -> Result<bool, Box<dyn std::error::Error>> {
+    let migrate = env::var("MIGRATE")
+        .ok()
+        .map(|migrate| migrate.parse::<bool>())
+        .transpose()
+        .context("Failed while parsing MIGRATE")?
+        .unwrap_or(true);
+
+    Ok(migrate)
+}
+
+#[cfg(feature = "cassandra")]
+fn layout() -> Result<Layout, Box<dyn std::error::Error>> {
+    let layout = env::var("LAYOUT")
+        .ok()
+        .map(|layout| layout.parse::<Layout>())
+        .transpose()?
+        .unwrap_or_default();
+
+    Ok(layout)
+}
+
+fn storage() -> Result<Storage, Box<dyn std::error::Error>> {
+    let storage = env::var("STORAGE")
+        .ok()
+        .map(|storage| storage.parse::<Storage>())
+        .transpose()?
+        .unwrap_or_default();
+
+    Ok(storage)
+}
+
+fn generator() -> Result<Generator, Box<dyn std::error::Error>> {
+    let generator = env::var("GENERATOR")
+        .ok()
+        .map(|generator| generator.parse::<Generator>())
+        .transpose()?
+        .unwrap_or_default();
+
+    Ok(generator)
+}
+
+#[cfg(feature = "cassandra")]
+async fn fun(State(state): State<AppState>) -> Result<impl IntoResponse, String> {
+    let cassandra = state.cassandra()?;
+
+    let report = migrate::run(cassandra.session(), cassandra.keyspace())
+        .await
+        .map_err(|err| err.to_string())?;
+
+    serde_json::to_string(&report).map_err(|err| err.to_string())
+}
+
+async fn stats(State(state): State<AppState>) -> Result<impl IntoResponse, String> {
+    serde_json::to_string(&state.stats.snapshot()).map_err(|err| err.to_string())
+}
+
+async fn reset_stats(State(state): State<AppState>) -> impl IntoResponse {
+    state.stats.reset();
+
+    "Stats Reset".to_string()
+}
+
+async fn metrics(State(state): State<AppState>) -> Result<impl IntoResponse, String> {
+    #[cfg(feature = "cassandra")]
+    if let Some(cassandra) = &state.cassandra {
+        state.metrics.sample_driver(cassandra.session());
+    }
+
+    state.metrics.render().map_err(|err| err.to_string())
+}
+
+#[tracing::instrument(skip_all, fields(payment_id, attempt_id))]
+async fn add_entry(
+    State(state): State<AppState>,
+    consistency: Overrides,
+    body: Bytes,
+) -> Result<impl IntoResponse, String> {
+    let store = state.store.clone().with_consistency(consistency);
+
+    let payment_attempt = match body.is_empty() {
+        true => {
+            let mut ctx = state.randr.lock().map_err(|err| err.to_string())?;
+            state.generator.generate(&mut ctx)
+        }
+        false => PaymentAttempt::from_body(&body).map_err(|err| err.to_string())?,
+    };
+
+    tracing::Span::current()
+        .record("payment_id", payment_attempt.payment_id.as_str())
+        .record("attempt_id", payment_attempt.attempt_id.as_str());
+
+    let output = state
+        .instrument(Operation::Insert, store.insert(payment_attempt))
+        .await;
 
     match output {
         Ok(value) => serde_json::to_string(&value).map_err(|err| err.to_string()),
@@ -75,60 +490,364 @@ async fn add_entry(State(state): State<Session>) -> Result<impl IntoResponse, St
     }
 }
 
+/// Most attempts `/create/batch` accepts in one call, generated or sent in the body.
+const MAX_BATCH_SIZE: usize = 1000;
+
+#[derive(Deserialize)]
+struct BatchParams {
+    /// Attempts to generate when no body is sent, at most `MAX_BATCH_SIZE`. Cassandra rejects
+    /// batches above `batch_size_fail_threshold_in_kb` (50 KiB by default) and a generated
+    /// attempt with its lookup entries takes a few KiB, so the default stays well below it.
+    /// Larger batches need a raised threshold, or the partitioned mode
+    #[serde(default = "default_batch_size")]
+    n: usize,
+    /// How inserts are grouped into `BATCH` statements, only with `STORAGE=cassandra`. Other
+    /// backends write the attempts through `insert_many`
+    #[cfg(feature = "cassandra")]
+    #[serde(default)]
+    mode: batch::Mode,
+    /// Consecutive generated attempts that share a `payment_id` and `merchant_id`
+    #[serde(default = "default_attempts_per_payment")]
+    per_payment: usize,
+}
+
+fn default_batch_size() -> usize {
+    10
+}
+
+fn default_attempts_per_payment() -> usize {
+    1
+}
+
+#[tracing::instrument(skip_all, fields(attempts))]
+async fn add_batch_entries(
+    State(state): State<AppState>,
+    consistency: Overrides,
+    Query(params): Query<BatchParams>,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let bad_request = |err: String| (StatusCode::BAD_REQUEST, err);
+
+    if params.n == 0 || params.per_payment == 0 {
+        return Err(bad_request(
+            "n and per_payment must be positive".to_string(),
+        ));
+    }
+
+    if params.n > MAX_BATCH_SIZE {
+        return Err(bad_request(format!("n must be at most {MAX_BATCH_SIZE}")));
+    }
+
+    let payment_attempts = match body.is_empty() {
+        true => {
+            let mut ctx = state
+                .randr
+                .lock()
+                .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+            let mut attempts: Vec<PaymentAttempt> = Vec::with_capacity(params.n);
+
+            for index in 0..params.n {
+                let mut attempt = state.generator.generate(&mut ctx);
+
+                if index % params.per_payment != 0 {
+                    if let Some(previous) = attempts.last() {
+                        attempt.payment_id.clone_from(&previous.payment_id);
+                        attempt.merchant_id.clone_from(&previous.merchant_id);
+                    }
+                }
+
+                attempts.push(attempt);
+            }
+
+            attempts
+        }
+        false => {
+            PaymentAttempt::from_batch_body(&body).map_err(|err| bad_request(err.to_string()))?
+        }
+    };
+
+    if payment_attempts.len() > MAX_BATCH_SIZE {
+        return Err(bad_request(format!(
+            "At most {MAX_BATCH_SIZE} attempts can be sent in one batch"
+        )));
+    }
+
+    tracing::Span::current().record("attempts", payment_attempts.len());
+
+    let internal_error =
+        |err: Box<dyn std::error::Error>| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string());
+
+    #[cfg(feature = "cassandra")]
+    if let Some(cassandra) = &state.cassandra {
+        let cassandra = cassandra.with_consistency(consistency);
+
+        return state
+            .instrument(
+                Operation::BatchInsert,
+                batch::insert(&payment_attempts, params.mode, &cassandra),
+            )
+            .await
+            .and_then(|report| Ok(serde_json::to_string(&report)?))
+            .map_err(internal_error);
+    }
+
+    let store = state.store.clone().with_consistency(consistency);
+    let start = tokio::time::Instant::now();
+
+    state
+        .instrument(Operation::BatchInsert, store.insert_many(&payment_attempts))
+        .await
+        .map(|()| {
+            serde_json::json!({
+                "attempts": payment_attempts.len(),
+                "total_us": start.elapsed().as_micros(),
+            })
+            .to_string()
+        })
+        .map_err(internal_error)
+}
+
+#[tracing::instrument(skip_all, fields(%payment_id, %attempt_id))]
 async fn retrieve_entry(
-    State(state): State<Session>,
+    State(state): State<AppState>,
+    consistency: Overrides,
     Path((payment_id, attempt_id)): Path<(String, String)>,
 ) -> Result<impl IntoResponse, String> {
-    let start = tokio::time::Instant::now();
-    let output = retrieve_data(payment_id, attempt_id, &state).await;
-    let duration = start.elapsed();
-    println!("[INFO] Retrieve Entry: {}", duration.as_micros());
+    let store = state.store.clone().with_consistency(consistency);
+
+    let output = state
+        .instrument(
+            Operation::PointRead,
+            retrieve_data(&payment_id, &attempt_id, store.as_ref()),
+        )
+        .await;
 
     match output {
-        Ok(_) => Ok("Success".to_string()),
+        Ok(value) => serde_json::to_string(&value).map_err(|err| err.to_string()),
         Err(err) => Err(err.to_string()),
     }
 }
 
-async fn create_table(session: &Session) -> Result<(), Box<dyn std::error::Error>> {
-    session.execute(include_str!("keyspace.cql")).await?;
-    session.execute(include_str!("schema.cql")).await?;
+#[derive(Deserialize)]
+struct ListParams {
+    /// Only return the most recently created attempt of the payment
+    #[serde(default)]
+    latest: bool,
+}
 
-    Ok(())
+#[tracing::instrument(skip_all, fields(%payment_id))]
+async fn list_entries(
+    State(state): State<AppState>,
+    consistency: Overrides,
+    Path(payment_id): Path<String>,
+    Query(params): Query<ListParams>,
+) -> Result<impl IntoResponse, String> {
+    let store = state.store.clone().with_consistency(consistency);
+
+    let output = state
+        .instrument(Operation::PartitionScan, store.list(&payment_id))
+        .await;
+
+    let mut attempts = output.map_err(|err| err.to_string())?;
+
+    if params.latest {
+        attempts = latest_attempt(attempts).into_iter().collect();
+    }
+
+    serde_json::to_string(&attempts).map_err(|err| err.to_string())
 }
 
-async fn add_data(session: &Session) -> Result<PaymentAttempt, Box<dyn std::error::Error>> {
-    let mut statement = session.statement(include_str!("insert_query.cql"));
+/// Most recently created of the attempts of a payment, as listed in `attempt_id` order.
+fn latest_attempt(attempts: Vec<PaymentAttempt>) -> Option<PaymentAttempt> {
+    // `max_by_key` keeps the last maximum, so ties go to the highest `attempt_id`
+    attempts
+        .into_iter()
+        .max_by_key(|attempt| attempt.created_at)
+}
+
+#[tracing::instrument(skip_all, fields(%merchant_id, %payment_id))]
+async fn list_merchant_entries(
+    State(state): State<AppState>,
+    consistency: Overrides,
+    Path((merchant_id, payment_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, String> {
+    let store = state.store.clone().with_consistency(consistency);
 
-    let payment_attempt = PaymentAttempt::randr(None, None);
+    let output = state
+        .instrument(
+            Operation::LookupByMerchant,
+            store.list_by_merchant(&merchant_id, &payment_id),
+        )
+        .await;
+
+    match output {
+        Ok(value) => serde_json::to_string(&value).map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
 
-    payment_attempt.populate_statement(&mut statement)?;
+#[tracing::instrument(skip_all, fields(%merchant_id, %connector_transaction_id))]
+async fn list_connector_transaction_entries(
+    State(state): State<AppState>,
+    consistency: Overrides,
+    Path((merchant_id, connector_transaction_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, String> {
+    let store = state.store.clone().with_consistency(consistency);
 
-    statement.execute().await?;
+    let output = state
+        .instrument(
+            Operation::LookupByConnectorTransaction,
+            store.list_by_connector_transaction(&merchant_id, &connector_transaction_id),
+        )
+        .await;
 
-    Ok(payment_attempt)
+    match output {
+        Ok(value) => serde_json::to_string(&value).map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[tracing::instrument(skip_all, fields(%payment_id, %attempt_id))]
+async fn update_entry(
+    State(state): State<AppState>,
+    consistency: Overrides,
+    Path((payment_id, attempt_id)): Path<(String, String)>,
+    Json(update): Json<PaymentAttemptUpdate>,
+) -> Result<impl IntoResponse, String> {
+    let store = state.store.clone().with_consistency(consistency);
+
+    let output = state
+        .instrument(
+            Operation::Update,
+            store.update(&payment_id, &attempt_id, update),
+        )
+        .await;
+
+    match output {
+        Ok(_) => Ok("Updated".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum DeleteMode {
+    /// Remove the rows, leaving tombstones behind
+    #[default]
+    Hard,
+    /// Keep the rows and mark them `Voided`, so no tombstones are written. Goes through an update,
+    /// so a missing attempt is not found rather than written as a partial row
+    Soft,
+}
+
+#[derive(Deserialize)]
+struct DeleteParams {
+    #[serde(default)]
+    mode: DeleteMode,
+}
+
+#[tracing::instrument(skip_all, fields(%payment_id, %attempt_id))]
+async fn delete_entry(
+    State(state): State<AppState>,
+    consistency: Overrides,
+    Path((payment_id, attempt_id)): Path<(String, String)>,
+    Query(params): Query<DeleteParams>,
+) -> Result<impl IntoResponse, String> {
+    let store = state.store.clone().with_consistency(consistency);
+
+    let output = state
+        .instrument(
+            Operation::Delete,
+            delete_data(&payment_id, &attempt_id, params.mode, store.as_ref()),
+        )
+        .await;
+
+    match output {
+        Ok(_) => Ok("Deleted".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[tracing::instrument(skip_all, fields(%payment_id))]
+async fn delete_partition_entries(
+    State(state): State<AppState>,
+    consistency: Overrides,
+    Path(payment_id): Path<String>,
+    Query(params): Query<DeleteParams>,
+) -> Result<impl IntoResponse, String> {
+    let store = state.store.clone().with_consistency(consistency);
+
+    let output = state
+        .instrument(
+            Operation::Delete,
+            delete_partition_data(&payment_id, params.mode, store.as_ref()),
+        )
+        .await;
+
+    match output {
+        Ok(_) => Ok("Deleted".to_string()),
+        Err(err) => Err(err.to_string()),
+    }
 }
 
 async fn retrieve_data(
-    payment_id: String,
-    attempt_id: String,
-    session: &Session,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut statement = session.statement(include_str!("select_query.cql"));
+    payment_id: &str,
+    attempt_id: &str,
+    store: &dyn PaymentAttemptStore,
+) -> Result<PaymentAttempt, Box<dyn std::error::Error>> {
+    let payment_attempt = store
+        .get(payment_id, attempt_id)
+        .await?
+        .context("No rows found")?;
 
-    statement.bind(0, payment_id.as_str())?;
-    statement.bind(1, attempt_id.as_str())?;
+    Ok(payment_attempt)
+}
 
-    let rows = statement.execute().await?;
+async fn delete_data(
+    payment_id: &str,
+    attempt_id: &str,
+    mode: DeleteMode,
+    store: &dyn PaymentAttemptStore,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match mode {
+        DeleteMode::Hard => store.delete(payment_id, attempt_id).await,
+        DeleteMode::Soft => {
+            store
+                .update(payment_id, attempt_id, soft_delete_update())
+                .await
+        }
+    }
+}
 
-    let mut rows = rows.iter();
+async fn delete_partition_data(
+    payment_id: &str,
+    mode: DeleteMode,
+    store: &dyn PaymentAttemptStore,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match mode {
+        DeleteMode::Hard => store.delete_payment(payment_id).await?,
+        DeleteMode::Soft => {
+            let attempt_ids = store.attempt_ids(payment_id).await?;
 
-    let row = rows.next().context("No rows found")?;
+            for attempt_id in attempt_ids {
+                store
+                    .update(payment_id, &attempt_id, soft_delete_update())
+                    .await?;
+            }
+        }
+    }
 
     Ok(())
 }
 
-#[derive(Serialize, Deserialize)]
+fn soft_delete_update() -> PaymentAttemptUpdate {
+    PaymentAttemptUpdate::StatusUpdate {
+        status: storage_enums::AttemptStatus::Voided,
+        updated_by: "soft_delete".to_string(),
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Randr)]
 struct PaymentAttempt {
     pub payment_id: String,
     pub merchant_id: String,
@@ -155,25 +874,31 @@ struct PaymentAttempt {
     pub cancellation_reason: Option<String>,
     pub amount_to_capture: Option<i64>,
     pub mandate_id: Option<String>,
+    #[randr(with = payloads::browser_info)]
     pub browser_info: Option<serde_json::Value>,
     pub error_code: Option<String>,
     pub payment_token: Option<String>,
+    #[randr(with = payloads::connector_metadata)]
     pub connector_metadata: Option<serde_json::Value>,
     pub payment_experience: Option<storage_enums::PaymentExperience>,
     pub payment_method_type: Option<storage_enums::PaymentMethodType>,
+    #[randr(with = payloads::payment_method_data)]
     pub payment_method_data: Option<serde_json::Value>,
     pub business_sub_label: Option<String>,
+    #[randr(with = payloads::straight_through_algorithm)]
     pub straight_through_algorithm: Option<serde_json::Value>,
     pub preprocessing_step_id: Option<String>,
     // providing a location to store mandate details intermediately for transaction
     pub mandate_details: Option<storage_enums::MandateDataType>,
     pub error_reason: Option<String>,
+    #[randr(range = 0..10, null_rate = 0.7)]
     pub multiple_capture_count: Option<i16>,
     // reference to the payment at connector side
     pub connector_response_reference_id: Option<String>,
     pub amount_capturable: i64,
     pub updated_by: String,
     pub merchant_connector_id: Option<String>,
+    #[randr(with = payloads::authentication_data)]
     pub authentication_data: Option<serde_json::Value>,
     pub encoded_data: Option<String>,
     pub unified_code: Option<String>,
@@ -190,183 +915,594 @@ struct PaymentAttempt {
     pub client_version: Option<String>,
 }
 
-impl Randr for PaymentAttempt {
-    fn default() -> Self
-    where
-        Self: Sized,
-    {
-        Self {
-            payment_id: Randr::randr(None, None),
-            merchant_id: Randr::randr(None, None),
-            attempt_id: Randr::randr(None, None),
-            status: Randr::randr(None, None),
-            amount: Randr::randr(None, None),
-            currency: Randr::randr(None, None),
-            save_to_locker: Randr::randr(None, None),
-            connector: Randr::randr(None, None),
-            error_message: Randr::randr(None, None),
-            offer_amount: Randr::randr(None, None),
-            surcharge_amount: Randr::randr(None, None),
-            tax_amount: Randr::randr(None, None),
-            payment_method_id: Randr::randr(None, None),
-            payment_method: Randr::randr(None, None),
-            connector_transaction_id: Randr::randr(None, None),
-            capture_method: Randr::randr(None, None),
-            capture_on: Randr::randr(None, None),
-            confirm: Randr::randr(None, None),
-            authentication_type: Randr::randr(None, None),
-            created_at: Randr::randr(None, None),
-            modified_at: Randr::randr(None, None),
-            last_synced: Randr::randr(None, None),
-            cancellation_reason: Randr::randr(None, None),
-            amount_to_capture: Randr::randr(None, None),
-            mandate_id: Randr::randr(None, None),
-            browser_info: Randr::randr(None, None),
-            error_code: Randr::randr(None, None),
-            payment_token: Randr::randr(None, None),
-            connector_metadata: Randr::randr(None, None),
-            payment_experience: Randr::randr(None, None),
-            payment_method_type: Randr::randr(None, None),
-            payment_method_data: Randr::randr(None, None),
-            business_sub_label: Randr::randr(None, None),
-            straight_through_algorithm: Randr::randr(None, None),
-            preprocessing_step_id: Randr::randr(None, None),
-            mandate_details: Randr::randr(None, None),
-            error_reason: Randr::randr(None, None),
-            multiple_capture_count: Randr::randr(None, None),
-            connector_response_reference_id: Randr::randr(None, None),
-            amount_capturable: Randr::randr(None, None),
-            updated_by: Randr::randr(None, None),
-            merchant_connector_id: Randr::randr(None, None),
-            authentication_data: Randr::randr(None, None),
-            encoded_data: Randr::randr(None, None),
-            unified_code: Randr::randr(None, None),
-            unified_message: Randr::randr(None, None),
-            net_amount: Randr::randr(None, None),
-            external_three_ds_authentication_attempted: Randr::randr(None, None),
-            authentication_connector: Randr::randr(None, None),
-            authentication_id: Randr::randr(None, None),
-            mandate_data: Randr::randr(None, None),
-            fingerprint_id: Randr::randr(None, None),
-            payment_method_billing_address_id: Randr::randr(None, None),
-            charge_id: Randr::randr(None, None),
-            client_source: Randr::randr(None, None),
-            client_version: Randr::randr(None, None),
+impl PaymentAttempt {
+    /// Random attempt that holds together as a payment: amounts add up, error details only
+    /// appear on failed attempts, and dependent fields are only set alongside what they depend
+    /// on.
+    fn consistent(ctx: &mut randr::Context) -> Self {
+        let mut attempt = <Self as Randr>::default(ctx);
+
+        attempt.amount = randr::range(ctx, 100..1_000_000);
+        attempt.offer_amount = attempt
+            .offer_amount
+            .map(|_| randr::range(ctx, 0..=attempt.amount / 10));
+        attempt.surcharge_amount = attempt
+            .surcharge_amount
+            .map(|_| randr::range(ctx, 0..=attempt.amount / 20));
+        attempt.tax_amount = attempt
+            .tax_amount
+            .map(|_| randr::range(ctx, 0..=attempt.amount / 5));
+
+        let net_amount = attempt.amount
+            + attempt.surcharge_amount.unwrap_or(0)
+            + attempt.tax_amount.unwrap_or(0);
+
+        attempt.net_amount = Some(net_amount);
+        attempt.amount_to_capture = attempt.amount_to_capture.map(|_| net_amount);
+        attempt.amount_capturable = match attempt.status.is_capturable() {
+            true => net_amount,
+            false => 0,
+        };
+
+        if attempt.status.is_failure() {
+            let (code, message) = *[
+                ("card_declined", "Your card was declined."),
+                ("insufficient_funds", "Your card has insufficient funds."),
+                ("expired_card", "Your card has expired."),
+                ("incorrect_cvc", "Your card's security code is incorrect."),
+                (
+                    "processing_error",
+                    "An error occurred while processing your card.",
+                ),
+            ]
+            .choose(ctx.rng())
+            .expect("Error while picking an error code");
+
+            attempt.error_code = Some(code.to_string());
+            attempt.error_message = Some(message.to_string());
+            attempt.error_reason = Some(message.to_string());
+            attempt.unified_code = Some(format!("UE_{}", randr::range(ctx, 1000..10000)));
+            attempt.unified_message = Some(message.to_string());
+        } else {
+            attempt.error_code = None;
+            attempt.error_message = None;
+            attempt.error_reason = None;
+            attempt.unified_code = None;
+            attempt.unified_message = None;
+        }
+
+        if !matches!(
+            attempt.status,
+            storage_enums::AttemptStatus::Voided | storage_enums::AttemptStatus::VoidInitiated
+        ) {
+            attempt.cancellation_reason = None;
+        }
+
+        attempt.modified_at =
+            attempt.created_at + time::Duration::seconds(randr::range(ctx, 0..3600));
+        attempt.last_synced = attempt.last_synced.map(|_| attempt.modified_at);
+
+        attempt.capture_on = match attempt.capture_method {
+            Some(storage_enums::CaptureMethod::Scheduled) => {
+                Some(attempt.created_at + time::Duration::days(randr::range(ctx, 1..8)))
+            }
+            _ => None,
+        };
+
+        if !matches!(
+            attempt.capture_method,
+            Some(storage_enums::CaptureMethod::ManualMultiple)
+        ) {
+            attempt.multiple_capture_count = None;
         }
+
+        match attempt.mandate_id {
+            Some(_) => {
+                let mandate_amount = match &mut attempt.mandate_details {
+                    Some(storage_enums::MandateDataType::SingleUse(data)) => Some(data),
+                    Some(storage_enums::MandateDataType::MultiUse(data)) => data.as_mut(),
+                    None => None,
+                };
+
+                if let Some(mandate_amount) = mandate_amount {
+                    mandate_amount.amount = attempt.amount;
+                    if let Some(currency) = attempt.currency {
+                        mandate_amount.currency = currency;
+                    }
+                }
+            }
+            None => {
+                attempt.mandate_details = None;
+                attempt.mandate_data = None;
+            }
+        }
+
+        attempt
+    }
+
+    fn from_body(body: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let payment_attempt: Self = serde_json::from_slice(body)?;
+
+        payment_attempt.validate()?;
+
+        Ok(payment_attempt)
+    }
+
+    /// JSON array of attempts, as sent to `/create/batch`.
+    fn from_batch_body(body: &[u8]) -> Result<Vec<Self>, Box<dyn std::error::Error>> {
+        let payment_attempts: Vec<Self> = serde_json::from_slice(body)?;
+
+        for payment_attempt in &payment_attempts {
+            payment_attempt.validate()?;
+        }
+
+        Ok(payment_attempts)
+    }
+
+    fn validate(&self) -> Result<(), Box<dyn std::error::Error>> {
+        for (name, value) in [
+            ("payment_id", &self.payment_id),
+            ("merchant_id", &self.merchant_id),
+            ("attempt_id", &self.attempt_id),
+            ("updated_by", &self.updated_by),
+        ] {
+            if value.is_empty() {
+                return Err(format!("{name} must not be empty").into());
+            }
+        }
+
+        for (name, value) in [
+            ("amount", Some(self.amount)),
+            ("amount_capturable", Some(self.amount_capturable)),
+            ("amount_to_capture", self.amount_to_capture),
+            ("offer_amount", self.offer_amount),
+            ("surcharge_amount", self.surcharge_amount),
+            ("tax_amount", self.tax_amount),
+            ("net_amount", self.net_amount),
+        ] {
+            if value.is_some_and(|value| value < 0) {
+                return Err(format!("{name} must not be negative").into());
+            }
+        }
+
+        if self.modified_at < self.created_at {
+            return Err("modified_at must not precede created_at".into());
+        }
+
+        Ok(())
     }
 }
 
+/// Encoding to and decoding from Cassandra rows.
+#[cfg(feature = "cassandra")]
 impl PaymentAttempt {
-    fn populate_statement(&self, stmt: &mut Statement) -> Result<(), Box<dyn std::error::Error>> {
-        stmt.bind(0, self.payment_id.as_str())?;
-        stmt.bind(1, self.merchant_id.as_str())?;
-        stmt.bind(2, self.attempt_id.as_str())?;
-        stmt.bind(3, enum_parse(&self.status)?.as_str())?;
-        stmt.bind(4, self.amount)?;
-        for_opt(stmt, &self.currency, 5)?;
-        e_for_opt(stmt, &self.save_to_locker, 6)?;
-
-        opt_string(stmt, &self.connector, 7)?;
-        opt_string(stmt, &self.error_message, 8)?;
-        e_for_opt(stmt, &self.offer_amount, 9)?;
-        e_for_opt(stmt, &self.surcharge_amount, 10)?;
-        e_for_opt(stmt, &self.tax_amount, 11)?;
-        opt_string(stmt, &self.payment_method_id, 12)?;
-        for_opt(stmt, &self.payment_method, 13)?;
-        opt_string(stmt, &self.connector_transaction_id, 14)?;
-        for_opt(stmt, &self.capture_method, 15)?;
-        for_opt(stmt, &self.capture_on, 16)?;
-        stmt.bind(17, self.confirm)?;
-        for_opt(stmt, &self.authentication_type, 18)?;
-        stmt.bind(19, enum_parse(&self.created_at)?.as_str())?;
-        stmt.bind(20, enum_parse(&self.modified_at)?.as_str())?;
-        for_opt(stmt, &self.last_synced, 21)?;
-
-        opt_string(stmt, &self.cancellation_reason, 22)?;
-        e_for_opt(stmt, &self.amount_to_capture, 23)?;
-        opt_string(stmt, &self.mandate_id, 24)?;
-        for_opt(stmt, &self.browser_info, 25)?;
-        opt_string(stmt, &self.error_code, 26)?;
-        opt_string(stmt, &self.payment_token, 27)?;
-        for_opt(stmt, &self.connector_metadata, 28)?;
-        for_opt(stmt, &self.payment_experience, 29)?;
-        for_opt(stmt, &self.payment_method_type, 30)?;
-        for_opt(stmt, &self.payment_method_data, 31)?;
-        opt_string(stmt, &self.business_sub_label, 32)?;
-
-        for_opt(stmt, &self.straight_through_algorithm, 33)?;
-        opt_string(stmt, &self.preprocessing_step_id, 34)?;
-        for_opt(stmt, &self.mandate_details, 35)?;
-        opt_string(stmt, &self.error_reason, 36)?;
-        e_for_opt(stmt, &self.multiple_capture_count, 37)?;
-        opt_string(stmt, &self.connector_response_reference_id, 38)?;
-        stmt.bind(39, self.amount_capturable)?;
-        stmt.bind(40, self.updated_by.as_str())?;
-        opt_string(stmt, &self.merchant_connector_id, 41)?;
-        for_opt(stmt, &self.authentication_data, 42)?;
-        opt_string(stmt, &self.encoded_data, 43)?;
-        opt_string(stmt, &self.unified_code, 44)?;
-        opt_string(stmt, &self.unified_message, 45)?;
-        e_for_opt(stmt, &self.net_amount, 46)?;
-        e_for_opt(stmt, &self.external_three_ds_authentication_attempted, 47)?;
-        opt_string(stmt, &self.authentication_connector, 48)?;
-        opt_string(stmt, &self.authentication_id, 49)?;
-        for_opt(stmt, &self.mandate_data, 50)?;
-        opt_string(stmt, &self.fingerprint_id, 51)?;
-        opt_string(stmt, &self.payment_method_billing_address_id, 52)?;
-        opt_string(stmt, &self.charge_id, 53)?;
-
-        opt_string(stmt, &self.client_source, 54)?;
-        opt_string(stmt, &self.client_version, 55)?;
+    fn populate_statement(
+        &self,
+        stmt: &mut Statement,
+        layout: Layout,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for (loc, column) in self.columns(layout)?.into_iter().enumerate() {
+            match column {
+                Some(column) => column.bind(stmt, loc)?,
+                None => {
+                    stmt.bind_null(loc)?;
+                }
+            }
+        }
 
         Ok(())
     }
+
+    /// Every column in the order of `insert_query.cql`, `None` for nulls.
+    fn columns(
+        &self,
+        layout: Layout,
+    ) -> Result<Vec<Option<Column<'_>>>, Box<dyn std::error::Error>> {
+        Ok(vec![
+            Some(self.payment_id.as_str().into()),
+            Some(self.merchant_id.as_str().into()),
+            Some(self.attempt_id.as_str().into()),
+            Some(layout.enum_column(&self.status)?),
+            Some(self.amount.into()),
+            encode_opt(&self.currency, |value| layout.enum_column(value))?,
+            self.save_to_locker.map(Column::from),
+            self.connector.as_deref().map(Column::from),
+            self.error_message.as_deref().map(Column::from),
+            self.offer_amount.map(Column::from),
+            self.surcharge_amount.map(Column::from),
+            self.tax_amount.map(Column::from),
+            self.payment_method_id.as_deref().map(Column::from),
+            encode_opt(&self.payment_method, |value| layout.enum_column(value))?,
+            self.connector_transaction_id.as_deref().map(Column::from),
+            encode_opt(&self.capture_method, |value| layout.enum_column(value))?,
+            encode_opt(&self.capture_on, |value| layout.time_column(value))?,
+            Some(self.confirm.into()),
+            encode_opt(&self.authentication_type, |value| layout.enum_column(value))?,
+            Some(layout.time_column(&self.created_at)?),
+            Some(layout.time_column(&self.modified_at)?),
+            encode_opt(&self.last_synced, |value| layout.time_column(value))?,
+            self.cancellation_reason.as_deref().map(Column::from),
+            self.amount_to_capture.map(Column::from),
+            self.mandate_id.as_deref().map(Column::from),
+            json_opt(&self.browser_info)?,
+            self.error_code.as_deref().map(Column::from),
+            self.payment_token.as_deref().map(Column::from),
+            json_opt(&self.connector_metadata)?,
+            encode_opt(&self.payment_experience, |value| layout.enum_column(value))?,
+            encode_opt(&self.payment_method_type, |value| layout.enum_column(value))?,
+            json_opt(&self.payment_method_data)?,
+            self.business_sub_label.as_deref().map(Column::from),
+            json_opt(&self.straight_through_algorithm)?,
+            self.preprocessing_step_id.as_deref().map(Column::from),
+            encode_opt(&self.mandate_details, |value| layout.nested_column(value))?,
+            self.error_reason.as_deref().map(Column::from),
+            self.multiple_capture_count.map(Column::from),
+            self.connector_response_reference_id
+                .as_deref()
+                .map(Column::from),
+            Some(self.amount_capturable.into()),
+            Some(self.updated_by.as_str().into()),
+            self.merchant_connector_id.as_deref().map(Column::from),
+            json_opt(&self.authentication_data)?,
+            self.encoded_data.as_deref().map(Column::from),
+            self.unified_code.as_deref().map(Column::from),
+            self.unified_message.as_deref().map(Column::from),
+            self.net_amount.map(Column::from),
+            self.external_three_ds_authentication_attempted
+                .map(Column::from),
+            self.authentication_connector.as_deref().map(Column::from),
+            self.authentication_id.as_deref().map(Column::from),
+            encode_opt(&self.mandate_data, |value| layout.nested_column(value))?,
+            self.fingerprint_id.as_deref().map(Column::from),
+            self.payment_method_billing_address_id
+                .as_deref()
+                .map(Column::from),
+            self.charge_id.as_deref().map(Column::from),
+            self.client_source.as_deref().map(Column::from),
+            self.client_version.as_deref().map(Column::from),
+        ])
+    }
+
+    fn from_row<R: Record>(row: &R, layout: Layout) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self {
+            payment_id: row.text("payment_id")?,
+            merchant_id: row.text("merchant_id")?,
+            attempt_id: row.text("attempt_id")?,
+            status: layout.get_enum(row, "status")?,
+            amount: row.big_int("amount")?,
+            currency: get_opt(row, "currency", |row, name| layout.get_enum(row, name))?,
+            save_to_locker: get_opt(row, "save_to_locker", R::boolean)?,
+            connector: get_opt(row, "connector", R::text)?,
+            error_message: get_opt(row, "error_message", R::text)?,
+            offer_amount: get_opt(row, "offer_amount", R::big_int)?,
+            surcharge_amount: get_opt(row, "surcharge_amount", R::big_int)?,
+            tax_amount: get_opt(row, "tax_amount", R::big_int)?,
+            payment_method_id: get_opt(row, "payment_method_id", R::text)?,
+            payment_method: get_opt(row, "payment_method", |row, name| {
+                layout.get_enum(row, name)
+            })?,
+            connector_transaction_id: get_opt(row, "connector_transaction_id", R::text)?,
+            capture_method: get_opt(row, "capture_method", |row, name| {
+                layout.get_enum(row, name)
+            })?,
+            capture_on: get_opt(row, "capture_on", |row, name| layout.get_time(row, name))?,
+            confirm: row.boolean("confirm")?,
+            authentication_type: get_opt(row, "authentication_type", |row, name| {
+                layout.get_enum(row, name)
+            })?,
+            created_at: layout.get_time(row, "created_at")?,
+            modified_at: layout.get_time(row, "modified_at")?,
+            last_synced: get_opt(row, "last_synced", |row, name| layout.get_time(row, name))?,
+            cancellation_reason: get_opt(row, "cancellation_reason", R::text)?,
+            amount_to_capture: get_opt(row, "amount_to_capture", R::big_int)?,
+            mandate_id: get_opt(row, "mandate_id", R::text)?,
+            browser_info: get_opt(row, "browser_info", get_json)?,
+            error_code: get_opt(row, "error_code", R::text)?,
+            payment_token: get_opt(row, "payment_token", R::text)?,
+            connector_metadata: get_opt(row, "connector_metadata", get_json)?,
+            payment_experience: get_opt(row, "payment_experience", |row, name| {
+                layout.get_enum(row, name)
+            })?,
+            payment_method_type: get_opt(row, "payment_method_type", |row, name| {
+                layout.get_enum(row, name)
+            })?,
+            payment_method_data: get_opt(row, "payment_method_data", get_json)?,
+            business_sub_label: get_opt(row, "business_sub_label", R::text)?,
+            straight_through_algorithm: get_opt(row, "straight_through_algorithm", get_json)?,
+            preprocessing_step_id: get_opt(row, "preprocessing_step_id", R::text)?,
+            mandate_details: get_opt(row, "mandate_details", |row, name| {
+                layout.get_nested(row, name)
+            })?,
+            error_reason: get_opt(row, "error_reason", R::text)?,
+            multiple_capture_count: get_opt(row, "multiple_capture_count", R::small_int)?,
+            connector_response_reference_id: get_opt(
+                row,
+                "connector_response_reference_id",
+                R::text,
+            )?,
+            amount_capturable: row.big_int("amount_capturable")?,
+            updated_by: row.text("updated_by")?,
+            merchant_connector_id: get_opt(row, "merchant_connector_id", R::text)?,
+            authentication_data: get_opt(row, "authentication_data", get_json)?,
+            encoded_data: get_opt(row, "encoded_data", R::text)?,
+            unified_code: get_opt(row, "unified_code", R::text)?,
+            unified_message: get_opt(row, "unified_message", R::text)?,
+            net_amount: get_opt(row, "net_amount", R::big_int)?,
+            external_three_ds_authentication_attempted: get_opt(
+                row,
+                "external_three_ds_authentication_attempted",
+                R::boolean,
+            )?,
+            authentication_connector: get_opt(row, "authentication_connector", R::text)?,
+            authentication_id: get_opt(row, "authentication_id", R::text)?,
+            mandate_data: get_opt(row, "mandate_data", |row, name| {
+                layout.get_nested(row, name)
+            })?,
+            fingerprint_id: get_opt(row, "fingerprint_id", R::text)?,
+            payment_method_billing_address_id: get_opt(
+                row,
+                "payment_method_billing_address_id",
+                R::text,
+            )?,
+            charge_id: get_opt(row, "charge_id", R::text)?,
+            client_source: get_opt(row, "client_source", R::text)?,
+            client_version: get_opt(row, "client_version", R::text)?,
+        })
+    }
 }
 
-fn enum_parse<T: serde::Serialize>(em: &T) -> Result<String, Box<dyn std::error::Error>> {
-    Ok(serde_json::to_string(em)?)
+#[cfg(feature = "cassandra")]
+fn encode_opt<T>(
+    value: &Option<T>,
+    encode: impl FnOnce(&T) -> Result<Column<'static>, Box<dyn std::error::Error>>,
+) -> Result<Option<Column<'static>>, Box<dyn std::error::Error>> {
+    value.as_ref().map(encode).transpose()
 }
 
-fn for_opt<T: serde::Serialize>(
-    stat: &mut Statement,
-    data: &Option<T>,
-    loc: usize,
-) -> Result<(), Box<dyn std::error::Error>>
-where
-{
-    match data {
-        Some(val) => stat.bind(loc, enum_parse(val)?.as_str())?,
-        None => stat.bind_null(loc)?,
-    };
+/// JSON documents are stored as text in every layout.
+#[cfg(feature = "cassandra")]
+fn json_opt<T: serde::Serialize>(
+    value: &Option<T>,
+) -> Result<Option<Column<'static>>, Box<dyn std::error::Error>> {
+    encode_opt(value, |value| Ok(serde_json::to_string(value)?.into()))
+}
 
-    Ok(())
+#[cfg(feature = "cassandra")]
+fn get_json<R: Record, T: serde::de::DeserializeOwned>(
+    row: &R,
+    name: &str,
+) -> Result<T, Box<dyn std::error::Error>> {
+    Ok(serde_json::from_str(&row.text(name)?)?)
 }
 
-fn opt_string(
-    stat: &mut Statement,
-    data: &Option<String>,
-    loc: usize,
-) -> Result<(), Box<dyn std::error::Error>> {
-    match data {
-        Some(val) => stat.bind(loc, val.as_str())?,
-        None => stat.bind_null(loc)?,
-    };
+#[cfg(feature = "cassandra")]
+fn get_opt<R: Record, T>(
+    row: &R,
+    name: &str,
+    decode: impl FnOnce(&R, &str) -> Result<T, Box<dyn std::error::Error>>,
+) -> Result<Option<T>, Box<dyn std::error::Error>> {
+    if row.is_null(name)? {
+        return Ok(None);
+    }
 
-    Ok(())
+    Ok(Some(decode(row, name)?))
 }
 
-fn e_for_opt<T: Clone>(
-    stat: &mut Statement,
-    data: &Option<T>,
-    loc: usize,
-) -> Result<(), Box<dyn std::error::Error>>
-where
-    cassandra_cpp::Statement: BindRustType<T>,
-{
-    match data {
-        Some(val) => stat.bind(loc, val.clone())?,
-        None => stat.bind_null(loc)?,
-    };
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "cassandra")]
+    use std::collections::HashMap;
 
-    Ok(())
+    use super::*;
+    #[cfg(feature = "cassandra")]
+    use crate::user_types::UserDefined;
+
+    /// Encoded columns of one attempt by name, as Cassandra would hand them back.
+    #[cfg(feature = "cassandra")]
+    pub(crate) struct Encoded<'a>(pub(crate) HashMap<&'static str, Option<Column<'a>>>);
+
+    #[cfg(feature = "cassandra")]
+    impl<'a> Encoded<'a> {
+        pub(crate) fn new(attempt: &'a PaymentAttempt, layout: Layout) -> Self {
+            let columns = attempt.columns(layout).unwrap();
+            let names = column_names();
+            assert_eq!(names.len(), columns.len());
+
+            Self(names.into_iter().zip(columns).collect())
+        }
+
+        fn column(&self, name: &str) -> Result<&Column<'a>, Box<dyn std::error::Error>> {
+            self.0
+                .get(name)
+                .ok_or(format!("Unknown column {name}"))?
+                .as_ref()
+                .ok_or(format!("Column {name} is null").into())
+        }
+    }
+
+    #[cfg(feature = "cassandra")]
+    impl Record for Encoded<'_> {
+        fn is_null(&self, name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+            Ok(self
+                .0
+                .get(name)
+                .ok_or(format!("Unknown column {name}"))?
+                .is_none())
+        }
+
+        fn text(&self, name: &str) -> Result<String, Box<dyn std::error::Error>> {
+            match self.column(name)? {
+                Column::Text(value) => Ok(value.to_string()),
+                _ => Err(format!("Column {name} is not text").into()),
+            }
+        }
+
+        fn big_int(&self, name: &str) -> Result<i64, Box<dyn std::error::Error>> {
+            match self.column(name)? {
+                Column::BigInt(value) | Column::Timestamp(value) => Ok(*value),
+                _ => Err(format!("Column {name} is not a bigint").into()),
+            }
+        }
+
+        fn small_int(&self, name: &str) -> Result<i16, Box<dyn std::error::Error>> {
+            match self.column(name)? {
+                Column::SmallInt(value) => Ok(*value),
+                _ => Err(format!("Column {name} is not a smallint").into()),
+            }
+        }
+
+        fn boolean(&self, name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+            match self.column(name)? {
+                Column::Boolean(value) => Ok(*value),
+                _ => Err(format!("Column {name} is not a boolean").into()),
+            }
+        }
+
+        fn user_type<T: UserDefined>(&self, name: &str) -> Result<T, Box<dyn std::error::Error>> {
+            Err(format!("Column {name} is a user-defined type, which needs the driver").into())
+        }
+    }
+
+    /// Column names in the order of `insert_query.cql`, which `columns` binds in.
+    #[cfg(feature = "cassandra")]
+    fn column_names() -> Vec<&'static str> {
+        let cql = query::INSERT.cql();
+        let start = cql.find('(').unwrap() + 1;
+        let end = cql.find(')').unwrap();
+
+        cql[start..end].split(',').map(str::trim).collect()
+    }
+
+    #[cfg(feature = "cassandra")]
+    fn attempts() -> Vec<PaymentAttempt> {
+        let mut ctx = randr::Context::new(Some(7));
+
+        [Generator::Random, Generator::Consistent]
+            .into_iter()
+            .flat_map(|generator| (0..50).map(move |_| generator))
+            .map(|generator| generator.generate(&mut ctx))
+            .collect()
+    }
+
+    #[cfg(feature = "cassandra")]
+    fn assert_round_trip(attempt: &PaymentAttempt, layout: Layout) {
+        let decoded = PaymentAttempt::from_row(&Encoded::new(attempt, layout), layout).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&decoded).unwrap(),
+            serde_json::to_value(attempt).unwrap()
+        );
+    }
+
+    #[cfg(feature = "cassandra")]
+    #[test]
+    fn text_layout_round_trips() {
+        for attempt in attempts() {
+            assert_round_trip(&attempt, Layout::Text);
+        }
+    }
+
+    #[cfg(feature = "cassandra")]
+    #[test]
+    fn native_layout_round_trips() {
+        let truncate = |value: PrimitiveDateTime| {
+            layout::from_millis(layout::millis(&value).unwrap()).unwrap()
+        };
+
+        for mut attempt in attempts() {
+            // User-defined types are encoded by the driver
+            attempt.mandate_details = None;
+            attempt.mandate_data = None;
+
+            attempt.created_at = truncate(attempt.created_at);
+            attempt.modified_at = truncate(attempt.modified_at);
+            attempt.capture_on = attempt.capture_on.map(truncate);
+            attempt.last_synced = attempt.last_synced.map(truncate);
+
+            assert_round_trip(&attempt, Layout::Native);
+        }
+    }
+
+    #[test]
+    fn consistent_attempts_hold_together() {
+        let mut ctx = randr::Context::new(Some(11));
+
+        for _ in 0..1000 {
+            let attempt = Generator::Consistent.generate(&mut ctx);
+            attempt.validate().unwrap();
+
+            let net_amount = attempt.amount
+                + attempt.surcharge_amount.unwrap_or(0)
+                + attempt.tax_amount.unwrap_or(0);
+            assert_eq!(attempt.net_amount, Some(net_amount));
+            assert!(attempt
+                .amount_to_capture
+                .is_none_or(|amount| amount == net_amount));
+            assert_eq!(
+                attempt.amount_capturable,
+                match attempt.status.is_capturable() {
+                    true => net_amount,
+                    false => 0,
+                }
+            );
+
+            let errors = [
+                &attempt.error_code,
+                &attempt.error_message,
+                &attempt.error_reason,
+                &attempt.unified_code,
+                &attempt.unified_message,
+            ];
+            match attempt.status.is_failure() {
+                true => assert!(errors.iter().all(|error| error.is_some())),
+                false => assert!(errors.iter().all(|error| error.is_none())),
+            }
+
+            if attempt.cancellation_reason.is_some() {
+                assert!(matches!(
+                    attempt.status,
+                    storage_enums::AttemptStatus::Voided
+                        | storage_enums::AttemptStatus::VoidInitiated
+                ));
+            }
+
+            assert!(attempt.modified_at >= attempt.created_at);
+            assert!(attempt
+                .last_synced
+                .is_none_or(|last_synced| last_synced == attempt.modified_at));
+
+            assert_eq!(
+                attempt.capture_on.is_some(),
+                matches!(
+                    attempt.capture_method,
+                    Some(storage_enums::CaptureMethod::Scheduled)
+                )
+            );
+            if attempt.multiple_capture_count.is_some() {
+                assert!(matches!(
+                    attempt.capture_method,
+                    Some(storage_enums::CaptureMethod::ManualMultiple)
+                ));
+            }
+
+            if attempt.mandate_id.is_none() {
+                assert!(attempt.mandate_details.is_none());
+                assert!(attempt.mandate_data.is_none());
+            }
+            let mandate_amount = match &attempt.mandate_details {
+                Some(storage_enums::MandateDataType::SingleUse(data)) => Some(data),
+                Some(storage_enums::MandateDataType::MultiUse(data)) => data.as_ref(),
+                None => None,
+            };
+            if let Some(mandate_amount) = mandate_amount {
+                assert_eq!(mandate_amount.amount, attempt.amount);
+                if let Some(currency) = attempt.currency {
+                    assert_eq!(
+                        serde_json::to_value(mandate_amount.currency).unwrap(),
+                        serde_json::to_value(currency).unwrap()
+                    );
+                }
+            }
+        }
+    }
 }
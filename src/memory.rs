@@ -0,0 +1,344 @@
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::BuildHasher;
+use std::sync::{Arc, RwLock};
+
+use axum::async_trait;
+
+use crate::consistency::Overrides;
+use crate::store::PaymentAttemptStore;
+use crate::update::PaymentAttemptUpdate;
+use crate::PaymentAttempt;
+
+/// Number of locks the partitions are spread over.
+const SHARDS: usize = 64;
+
+/// Store held in process memory, for running without a database. Attempts are grouped by
+/// `payment_id` and kept ordered by `attempt_id`, mirroring the partitions and clustering of
+/// `PRIMARY KEY ((payment_id), attempt_id)`. Partitions are spread over shards with a lock each,
+/// so requests for different payments rarely wait on one another.
+pub struct MemoryStore {
+    shards: Vec<RwLock<Partitions>>,
+    hasher: RandomState,
+}
+
+type Partitions = HashMap<String, BTreeMap<String, PaymentAttempt>>;
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| RwLock::default()).collect(),
+            hasher: RandomState::new(),
+        }
+    }
+}
+
+impl MemoryStore {
+    /// Shard holding the partition of `payment_id`.
+    fn shard(&self, payment_id: &str) -> &RwLock<Partitions> {
+        let hash = self.hasher.hash_one(payment_id);
+        &self.shards[(hash % SHARDS as u64) as usize]
+    }
+}
+
+#[async_trait]
+impl PaymentAttemptStore for MemoryStore {
+    fn with_consistency(self: Arc<Self>, _overrides: Overrides) -> Arc<dyn PaymentAttemptStore> {
+        self
+    }
+
+    async fn insert(
+        &self,
+        payment_attempt: PaymentAttempt,
+    ) -> Result<PaymentAttempt, Box<dyn std::error::Error>> {
+        let mut partitions = self
+            .shard(&payment_attempt.payment_id)
+            .write()
+            .map_err(|err| err.to_string())?;
+
+        partitions
+            .entry(payment_attempt.payment_id.clone())
+            .or_default()
+            .insert(payment_attempt.attempt_id.clone(), payment_attempt.clone());
+
+        Ok(payment_attempt)
+    }
+
+    async fn insert_many(
+        &self,
+        payment_attempts: &[PaymentAttempt],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for payment_attempt in payment_attempts {
+            self.insert(payment_attempt.clone()).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn get(
+        &self,
+        payment_id: &str,
+        attempt_id: &str,
+    ) -> Result<Option<PaymentAttempt>, Box<dyn std::error::Error>> {
+        let partitions = self
+            .shard(payment_id)
+            .read()
+            .map_err(|err| err.to_string())?;
+
+        Ok(partitions
+            .get(payment_id)
+            .and_then(|partition| partition.get(attempt_id))
+            .cloned())
+    }
+
+    async fn list(
+        &self,
+        payment_id: &str,
+    ) -> Result<Vec<PaymentAttempt>, Box<dyn std::error::Error>> {
+        let partitions = self
+            .shard(payment_id)
+            .read()
+            .map_err(|err| err.to_string())?;
+
+        Ok(partitions
+            .get(payment_id)
+            .map(|partition| partition.values().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn attempt_ids(
+        &self,
+        payment_id: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let partitions = self
+            .shard(payment_id)
+            .read()
+            .map_err(|err| err.to_string())?;
+
+        Ok(partitions
+            .get(payment_id)
+            .map(|partition| partition.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn list_by_merchant(
+        &self,
+        merchant_id: &str,
+        payment_id: &str,
+    ) -> Result<Vec<PaymentAttempt>, Box<dyn std::error::Error>> {
+        let mut attempts = self.list(payment_id).await?;
+        attempts.retain(|attempt| attempt.merchant_id == merchant_id);
+
+        Ok(attempts)
+    }
+
+    /// Scans every partition, there is no index on the connector transaction id.
+    async fn list_by_connector_transaction(
+        &self,
+        merchant_id: &str,
+        connector_transaction_id: &str,
+    ) -> Result<Vec<PaymentAttempt>, Box<dyn std::error::Error>> {
+        let mut attempts = Vec::new();
+
+        for shard in &self.shards {
+            let partitions = shard.read().map_err(|err| err.to_string())?;
+
+            attempts.extend(
+                partitions
+                    .values()
+                    .flat_map(BTreeMap::values)
+                    .filter(|attempt| {
+                        attempt.merchant_id == merchant_id
+                            && attempt.connector_transaction_id.as_deref()
+                                == Some(connector_transaction_id)
+                    })
+                    .cloned(),
+            );
+        }
+
+        Ok(attempts)
+    }
+
+    async fn update(
+        &self,
+        payment_id: &str,
+        attempt_id: &str,
+        update: PaymentAttemptUpdate,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut partitions = self
+            .shard(payment_id)
+            .write()
+            .map_err(|err| err.to_string())?;
+
+        let attempt = partitions
+            .get_mut(payment_id)
+            .and_then(|partition| partition.get_mut(attempt_id))
+            .ok_or("No rows found")?;
+
+        update.apply(attempt);
+
+        Ok(())
+    }
+
+    async fn delete(
+        &self,
+        payment_id: &str,
+        attempt_id: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut partitions = self
+            .shard(payment_id)
+            .write()
+            .map_err(|err| err.to_string())?;
+
+        if let Some(partition) = partitions.get_mut(payment_id) {
+            partition.remove(attempt_id);
+
+            if partition.is_empty() {
+                partitions.remove(payment_id);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete_payment(&self, payment_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut partitions = self
+            .shard(payment_id)
+            .write()
+            .map_err(|err| err.to_string())?;
+
+        partitions.remove(payment_id);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::randr::Context;
+    use crate::{storage_enums, Generator};
+
+    fn attempt(ctx: &mut Context, payment_id: &str, attempt_id: &str) -> PaymentAttempt {
+        let mut attempt = Generator::Random.generate(ctx);
+        attempt.payment_id = payment_id.to_string();
+        attempt.attempt_id = attempt_id.to_string();
+        attempt
+    }
+
+    fn ids(attempts: &[PaymentAttempt]) -> Vec<&str> {
+        attempts
+            .iter()
+            .map(|attempt| attempt.attempt_id.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn partitions_are_ordered_by_attempt_id() {
+        let mut ctx = Context::new(Some(1));
+        let store = MemoryStore::default();
+
+        let attempts = [
+            attempt(&mut ctx, "pay_1", "att_c"),
+            attempt(&mut ctx, "pay_1", "att_a"),
+            attempt(&mut ctx, "pay_2", "att_0"),
+            attempt(&mut ctx, "pay_1", "att_b"),
+        ];
+        store.insert_many(&attempts).await.unwrap();
+
+        let listed = store.list("pay_1").await.unwrap();
+        assert_eq!(ids(&listed), ["att_a", "att_b", "att_c"]);
+        assert_eq!(
+            store.attempt_ids("pay_1").await.unwrap(),
+            ["att_a", "att_b", "att_c"]
+        );
+        assert!(store.list("pay_3").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn latest_attempt_is_the_last_created() {
+        let mut ctx = Context::new(Some(2));
+        let store = MemoryStore::default();
+
+        let mut first = attempt(&mut ctx, "pay_1", "att_b");
+        let mut second = attempt(&mut ctx, "pay_1", "att_a");
+        let mut tied = attempt(&mut ctx, "pay_1", "att_c");
+        first.created_at = ctx.now();
+        second.created_at = ctx.now();
+        tied.created_at = second.created_at;
+        store.insert_many(&[first, second, tied]).await.unwrap();
+
+        let latest = crate::latest_attempt(store.list("pay_1").await.unwrap()).unwrap();
+        assert_eq!(latest.attempt_id, "att_c");
+        assert!(crate::latest_attempt(Vec::new()).is_none());
+    }
+
+    #[tokio::test]
+    async fn missing_attempts_are_not_written() {
+        let mut ctx = Context::new(Some(3));
+        let store = MemoryStore::default();
+        store
+            .insert(attempt(&mut ctx, "pay_1", "att_a"))
+            .await
+            .unwrap();
+
+        let update = PaymentAttemptUpdate::StatusUpdate {
+            status: storage_enums::AttemptStatus::Charged,
+            updated_by: "test".to_string(),
+        };
+        let err = store.update("pay_1", "att_b", update).await.unwrap_err();
+        assert_eq!(err.to_string(), "No rows found");
+        assert!(store.get("pay_1", "att_b").await.unwrap().is_none());
+
+        store.delete("pay_1", "att_b").await.unwrap();
+        store.delete("pay_2", "att_a").await.unwrap();
+        store.delete_payment("pay_2").await.unwrap();
+        assert_eq!(ids(&store.list("pay_1").await.unwrap()), ["att_a"]);
+    }
+
+    #[tokio::test]
+    async fn updates_apply_to_the_stored_attempt() {
+        let mut ctx = Context::new(Some(4));
+        let store = MemoryStore::default();
+        store
+            .insert(attempt(&mut ctx, "pay_1", "att_a"))
+            .await
+            .unwrap();
+
+        let update = PaymentAttemptUpdate::StatusUpdate {
+            status: storage_enums::AttemptStatus::Voided,
+            updated_by: "test".to_string(),
+        };
+        store.update("pay_1", "att_a", update).await.unwrap();
+
+        let attempt = store.get("pay_1", "att_a").await.unwrap().unwrap();
+        assert!(matches!(
+            attempt.status,
+            storage_enums::AttemptStatus::Voided
+        ));
+        assert_eq!(attempt.updated_by, "test");
+    }
+
+    #[tokio::test]
+    async fn deleting_a_payment_removes_only_its_partition() {
+        let mut ctx = Context::new(Some(5));
+        let store = MemoryStore::default();
+        store
+            .insert_many(&[
+                attempt(&mut ctx, "pay_1", "att_a"),
+                attempt(&mut ctx, "pay_1", "att_b"),
+                attempt(&mut ctx, "pay_2", "att_a"),
+            ])
+            .await
+            .unwrap();
+
+        store.delete_payment("pay_1").await.unwrap();
+
+        assert!(store.list("pay_1").await.unwrap().is_empty());
+        assert!(store.get("pay_1", "att_a").await.unwrap().is_none());
+        assert_eq!(ids(&store.list("pay_2").await.unwrap()), ["att_a"]);
+
+        store.delete("pay_2", "att_a").await.unwrap();
+        assert!(store.list("pay_2").await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,304 @@
+use axum::extract::{MatchedPath, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+#[cfg(feature = "cassandra")]
+use cassandra_cpp::{ErrorKind, Session};
+#[cfg(feature = "cassandra")]
+use prometheus::Gauge;
+use prometheus::{
+    exponential_buckets, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use std::sync::Arc;
+use tokio::time::Instant;
+
+/// Prometheus metrics of the server, every name is prefixed with `casec_`.
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    /// `STORAGE` backend that store calls are labelled with
+    backend: &'static str,
+    store_errors: IntCounterVec,
+    store_in_flight: IntGaugeVec,
+    #[cfg(feature = "cassandra")]
+    driver: Driver,
+}
+
+impl Metrics {
+    pub fn new(backend: &'static str) -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("casec".to_string()), None)?;
+
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled, by route"),
+            &["method", "route"],
+        )?;
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency, by route",
+            )
+            .buckets(exponential_buckets(0.0001, 2.0, 16)?),
+            &["method", "route"],
+        )?;
+        let store_errors = IntCounterVec::new(
+            Opts::new(
+                "store_errors_total",
+                "Failed store calls, by backend and error code",
+            ),
+            &["backend", "kind"],
+        )?;
+        let store_in_flight = IntGaugeVec::new(
+            Opts::new(
+                "store_in_flight_requests",
+                "Store calls currently awaited by handlers, by backend",
+            ),
+            &["backend"],
+        )?;
+        registry.register(Box::new(requests.clone()))?;
+        registry.register(Box::new(request_duration.clone()))?;
+        registry.register(Box::new(store_errors.clone()))?;
+        registry.register(Box::new(store_in_flight.clone()))?;
+        #[cfg(feature = "cassandra")]
+        let driver = Driver::register(&registry)?;
+
+        Ok(Self {
+            registry,
+            requests,
+            request_duration,
+            backend,
+            store_errors,
+            store_in_flight,
+            #[cfg(feature = "cassandra")]
+            driver,
+        })
+    }
+
+    /// Counts a store call as in flight until the returned guard is dropped, which also covers
+    /// handlers dropped before the call finished.
+    pub fn store_started(&self) -> InFlight {
+        let gauge = self.store_in_flight.with_label_values(&[self.backend]);
+        gauge.inc();
+
+        InFlight(gauge)
+    }
+
+    pub fn store_failed(&self, error: &(dyn std::error::Error + 'static)) {
+        self.store_errors
+            .with_label_values(&[self.backend, &error_kind(error)])
+            .inc();
+    }
+
+    /// Renders everything in the text format.
+    pub fn render(&self) -> prometheus::Result<String> {
+        TextEncoder::new().encode_to_string(&self.registry.gather())
+    }
+
+    /// Samples the driver's session metrics, to be rendered next.
+    #[cfg(feature = "cassandra")]
+    pub fn sample_driver(&self, session: &Session) {
+        self.driver.sample(session);
+    }
+}
+
+/// Gauges sampled from the driver's session metrics.
+#[cfg(feature = "cassandra")]
+struct Driver {
+    connections: IntGaugeVec,
+    timeouts: IntGaugeVec,
+    water_marks: IntGaugeVec,
+    latency: IntGaugeVec,
+    rate: Gauge,
+}
+
+#[cfg(feature = "cassandra")]
+impl Driver {
+    fn register(registry: &Registry) -> prometheus::Result<Self> {
+        let connections = IntGaugeVec::new(
+            Opts::new("driver_connections", "Driver connections to the cluster"),
+            &["state"],
+        )?;
+        let timeouts = IntGaugeVec::new(
+            Opts::new(
+                "driver_timeouts",
+                "Timeouts reported by the driver, by kind",
+            ),
+            &["kind"],
+        )?;
+        let water_marks = IntGaugeVec::new(
+            Opts::new(
+                "driver_exceeded_water_marks",
+                "Times the driver exceeded a connection water mark",
+            ),
+            &["water_mark"],
+        )?;
+        let latency = IntGaugeVec::new(
+            Opts::new(
+                "driver_request_latency_microseconds",
+                "Request latency as measured by the driver",
+            ),
+            &["quantile"],
+        )?;
+        let rate = Gauge::new(
+            "driver_requests_per_second",
+            "One minute request rate as measured by the driver",
+        )?;
+
+        registry.register(Box::new(connections.clone()))?;
+        registry.register(Box::new(timeouts.clone()))?;
+        registry.register(Box::new(water_marks.clone()))?;
+        registry.register(Box::new(latency.clone()))?;
+        registry.register(Box::new(rate.clone()))?;
+
+        Ok(Self {
+            connections,
+            timeouts,
+            water_marks,
+            latency,
+            rate,
+        })
+    }
+
+    fn sample(&self, session: &Session) {
+        let driver = session.get_metrics();
+
+        for (state, value) in [
+            ("total", driver.total_connections),
+            ("available", driver.available_connections),
+        ] {
+            self.connections
+                .with_label_values(&[state])
+                .set(gauge(value));
+        }
+
+        for (kind, value) in [
+            ("connection", driver.connection_timeouts),
+            ("pending_request", driver.pending_request_timeouts),
+            ("request", driver.request_timeouts),
+        ] {
+            self.timeouts.with_label_values(&[kind]).set(gauge(value));
+        }
+
+        for (water_mark, value) in [
+            (
+                "pending_requests",
+                driver.exceeded_pending_requests_water_mark,
+            ),
+            ("write_bytes", driver.exceeded_write_bytes_water_mark),
+        ] {
+            self.water_marks
+                .with_label_values(&[water_mark])
+                .set(gauge(value));
+        }
+
+        for (quantile, value) in [
+            ("0.5", driver.median_us),
+            ("0.75", driver.percentile_75th_us),
+            ("0.95", driver.percentile_95th_us),
+            ("0.99", driver.percentile_99th_us),
+            ("0.999", driver.percentile_999th_us),
+            ("1", driver.max_us),
+        ] {
+            self.latency
+                .with_label_values(&[quantile])
+                .set(gauge(value));
+        }
+
+        self.rate.set(driver.one_minute_rate_per_seq);
+    }
+}
+
+/// A store call counted in `store_in_flight_requests`, uncounted on drop.
+pub struct InFlight(IntGauge);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// Middleware counting and timing requests by their matched route, so that path parameters
+/// do not blow up the label cardinality. Handlers answer failures with a message rather than a
+/// status code, so requests are not labelled by status; failed store calls are counted in
+/// `store_errors_total` instead.
+pub async fn track(State(metrics): State<Arc<Metrics>>, request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched".to_string(), |path| path.as_str().to_string());
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+
+    metrics
+        .request_duration
+        .with_label_values(&[&method, &route])
+        .observe(start.elapsed().as_secs_f64());
+    metrics.requests.with_label_values(&[&method, &route]).inc();
+
+    response
+}
+
+/// Driver error code of a failed call, the SQLSTATE for Postgres, or `other` for errors that
+/// did not come from a driver.
+fn error_kind(error: &(dyn std::error::Error + 'static)) -> String {
+    if let Some(code) = error
+        .downcast_ref::<tokio_postgres::Error>()
+        .and_then(tokio_postgres::Error::code)
+    {
+        return code.code().to_string();
+    }
+
+    #[cfg(feature = "cassandra")]
+    if let Some(error) = error.downcast_ref::<cassandra_cpp::Error>() {
+        return match error.kind() {
+            ErrorKind::CassError(code, _) | ErrorKind::CassErrorResult(code, ..) => {
+                format!("{code:?}").to_lowercase()
+            }
+            ErrorKind::UnsupportedType(..) => "unsupported_type".to_string(),
+            _ => "other".to_string(),
+        };
+    }
+
+    "other".to_string()
+}
+
+#[cfg(feature = "cassandra")]
+fn gauge(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_calls_are_labelled_by_backend() {
+        let metrics = Metrics::new("memory").unwrap();
+        let error: Box<dyn std::error::Error> = "No rows found".into();
+
+        let finished = metrics.store_started();
+        metrics.store_failed(error.as_ref());
+        drop(finished);
+        let _in_flight = metrics.store_started();
+
+        let rendered = metrics.render().unwrap();
+        assert!(rendered.contains(r#"casec_store_errors_total{backend="memory",kind="other"} 1"#));
+        assert!(rendered.contains(r#"casec_store_in_flight_requests{backend="memory"} 1"#));
+    }
+
+    #[tokio::test]
+    async fn dropped_store_calls_leave_the_in_flight_count() {
+        let metrics = Metrics::new("memory").unwrap();
+
+        let call = async {
+            let _in_flight = metrics.store_started();
+            std::future::pending::<()>().await;
+        };
+        let _ = tokio::time::timeout(std::time::Duration::from_millis(1), call).await;
+
+        let rendered = metrics.render().unwrap();
+        assert!(rendered.contains(r#"casec_store_in_flight_requests{backend="memory"} 0"#));
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::BTreeMap;
+
+use cassandra_cpp::{AsRustType, BindRustType, LendingIterator, Session};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use crate::keyspace::Keyspace;
+
+/// Schema changes, applied in order of `version` and never edited once released. The first
+/// migrations only use `IF NOT EXISTS`, so that a keyspace created before migrations existed is
+/// adopted as is.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "payment_attempts",
+        cql: include_str!("migrations/0001_payment_attempts.cql"),
+    },
+    Migration {
+        version: 2,
+        name: "lookup_tables",
+        cql: include_str!("migrations/0002_lookup_tables.cql"),
+    },
+    Migration {
+        version: 3,
+        name: "mandate_types",
+        cql: include_str!("migrations/0003_mandate_types.cql"),
+    },
+    Migration {
+        version: 4,
+        name: "payment_attempts_native",
+        cql: include_str!("migrations/0004_payment_attempts_native.cql"),
+    },
+];
+
+struct Migration {
+    version: i32,
+    name: &'static str,
+    /// One or more statements, each terminated by `;`
+    cql: &'static str,
+}
+
+impl Migration {
+    /// Hex encoded SHA-256 of the statements as run against `keyspace`, recorded when the
+    /// migration is applied.
+    fn checksum(&self, keyspace: &Keyspace) -> String {
+        Sha256::digest(keyspace.render(self.cql).as_bytes())
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    fn statements(&self, keyspace: &Keyspace) -> Vec<String> {
+        keyspace
+            .render(self.cql)
+            .split(';')
+            .map(str::trim)
+            .filter(|statement| !statement.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+/// A migration recorded in `schema_migrations`.
+struct Applied {
+    name: String,
+    checksum: String,
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    /// Versions that were already applied
+    current: Vec<i32>,
+    /// Versions applied by this run
+    applied: Vec<i32>,
+}
+
+/// Brings the keyspace up to date, applying every pending migration in order. Fails before
+/// applying anything when an applied migration was edited or is unknown to this build.
+/// Concurrent runs are not coordinated, start a single instance or run `casec migrate` first.
+pub async fn run(
+    session: &Session,
+    keyspace: &Keyspace,
+) -> Result<Report, Box<dyn std::error::Error>> {
+    // Neither the keyspace nor `schema_migrations` is versioned
+    for statement in keyspace.statements() {
+        session.execute(statement).await?;
+    }
+    session
+        .execute(keyspace.render(include_str!("migrations/schema_migrations.cql")))
+        .await?;
+
+    let pending = pending(&applied(session, keyspace).await?, keyspace)?;
+
+    let current = MIGRATIONS
+        .iter()
+        .map(|migration| migration.version)
+        .filter(|version| !pending.contains(version))
+        .collect();
+    let mut applied = Vec::new();
+
+    for migration in MIGRATIONS
+        .iter()
+        .filter(|migration| pending.contains(&migration.version))
+    {
+        info!(
+            version = migration.version,
+            name = migration.name,
+            "applying migration"
+        );
+
+        for statement in migration.statements(keyspace) {
+            session.execute(statement).await?;
+        }
+
+        let mut statement = session.statement(keyspace.render(
+            "INSERT INTO {keyspace}.schema_migrations (version, name, checksum, applied_at) \
+             VALUES (?, ?, ?, toTimestamp(now()))",
+        ));
+        statement.bind(0, migration.version)?;
+        statement.bind(1, migration.name)?;
+        statement.bind(2, migration.checksum(keyspace).as_str())?;
+        statement.execute().await?;
+
+        applied.push(migration.version);
+    }
+
+    Ok(Report { current, applied })
+}
+
+/// Fails unless every migration has been applied, for running without `MIGRATE`. Only reads,
+/// a keyspace without `schema_migrations` has every migration pending.
+pub async fn verify(
+    session: &Session,
+    keyspace: &Keyspace,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pending = pending(&applied(session, keyspace).await?, keyspace)?;
+
+    if !pending.is_empty() {
+        return Err(format!(
+            "Pending migrations {pending:?}, run `casec migrate` or start with MIGRATE=true"
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Versions not applied yet, after checking that the applied ones match this build.
+fn pending(
+    applied: &BTreeMap<i32, Applied>,
+    keyspace: &Keyspace,
+) -> Result<Vec<i32>, Box<dyn std::error::Error>> {
+    for (version, applied) in applied {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|migration| migration.version == *version)
+            .ok_or_else(|| {
+                format!(
+                    "Migration {version} ({}) is applied but unknown to this build",
+                    applied.name
+                )
+            })?;
+
+        if migration.checksum(keyspace) != applied.checksum {
+            return Err(format!(
+                "Migration {version} ({}) was edited after it was applied, add a new migration instead",
+                migration.name
+            )
+            .into());
+        }
+    }
+
+    Ok(MIGRATIONS
+        .iter()
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains_key(version))
+        .collect())
+}
+
+/// Migrations recorded in `schema_migrations`, none when the table does not exist.
+async fn applied(
+    session: &Session,
+    keyspace: &Keyspace,
+) -> Result<BTreeMap<i32, Applied>, Box<dyn std::error::Error>> {
+    let mut statement = session.statement(
+        "SELECT table_name FROM system_schema.tables WHERE keyspace_name = ? AND table_name = 'schema_migrations'",
+    );
+    statement.bind(0, keyspace.name())?;
+
+    if statement.execute().await?.first_row().is_none() {
+        return Ok(BTreeMap::new());
+    }
+
+    let rows = session
+        .execute(
+            keyspace.render("SELECT version, name, checksum FROM {keyspace}.schema_migrations"),
+        )
+        .await?;
+
+    let mut applied = BTreeMap::new();
+    let mut iter = rows.iter();
+    while let Some(row) = iter.next() {
+        let version: i32 = row.get_by_name("version")?;
+        applied.insert(
+            version,
+            Applied {
+                name: row.get_by_name("name")?,
+                checksum: row.get_by_name("checksum")?,
+            },
+        );
+    }
+
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyspace(name: &str) -> Keyspace {
+        Keyspace::new(name.to_string(), None, None).unwrap()
+    }
+
+    fn applied(migration: &Migration, checksum: String) -> (i32, Applied) {
+        (
+            migration.version,
+            Applied {
+                name: migration.name.to_string(),
+                checksum,
+            },
+        )
+    }
+
+    #[test]
+    fn versions_ascend_without_gaps() {
+        let versions: Vec<i32> = MIGRATIONS
+            .iter()
+            .map(|migration| migration.version)
+            .collect();
+
+        assert_eq!(versions, (1..=MIGRATIONS.len() as i32).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn released_migrations_are_unchanged() {
+        let keyspace = keyspace("payments");
+        let checksums: Vec<String> = MIGRATIONS
+            .iter()
+            .map(|migration| migration.checksum(&keyspace))
+            .collect();
+
+        // Changing one of these means a released migration was edited, add a new one instead
+        assert_eq!(
+            checksums,
+            [
+                "f89ecf2a2345e724abbc9fe3fbcb3080f0f70aad190b847d73110d37bf3face4",
+                "4d89adbd100d70ee138f6e0096048b9ed788b45770888d35f452b46a2d1c0a3d",
+                "c04a4c6463bab74a3ebc7ece1cb371cb34fba730cceda83106f8b5ea044c4c28",
+                "4e4564d24af389fd20428afac617badece58b95ed1c3fe8b566bc157bdbf32ac",
+            ]
+        );
+    }
+
+    #[test]
+    fn checksums_depend_on_the_keyspace() {
+        for migration in MIGRATIONS {
+            assert_ne!(
+                migration.checksum(&keyspace("payments")),
+                migration.checksum(&keyspace("payments_bench"))
+            );
+        }
+    }
+
+    #[test]
+    fn statements_are_split_and_rendered() {
+        let keyspace = keyspace("payments");
+
+        for migration in MIGRATIONS {
+            let statements = migration.statements(&keyspace);
+
+            assert!(!statements.is_empty());
+            for statement in statements {
+                assert!(!statement.contains(';'), "{statement}");
+                assert!(!statement.contains("{keyspace}"), "{statement}");
+            }
+        }
+    }
+
+    #[test]
+    fn pending_skips_applied_migrations() {
+        let keyspace = keyspace("payments");
+        let applied = BTreeMap::from([applied(&MIGRATIONS[0], MIGRATIONS[0].checksum(&keyspace))]);
+
+        assert_eq!(pending(&applied, &keyspace).unwrap(), [2, 3, 4]);
+        assert_eq!(pending(&BTreeMap::new(), &keyspace).unwrap(), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn pending_rejects_edited_and_unknown_migrations() {
+        let keyspace = keyspace("payments");
+
+        let edited = BTreeMap::from([applied(&MIGRATIONS[0], "edited".to_string())]);
+        assert!(pending(&edited, &keyspace)
+            .unwrap_err()
+            .to_string()
+            .contains("was edited"));
+
+        let unknown = BTreeMap::from([(
+            99,
+            Applied {
+                name: "unknown".to_string(),
+                checksum: String::new(),
+            },
+        )]);
+        assert!(pending(&unknown, &keyspace)
+            .unwrap_err()
+            .to_string()
+            .contains("unknown to this build"));
+    }
+}
@@ -0,0 +1,335 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde_json::{json, Map, Value};
+
+use crate::randr::{self, Context};
+
+const USER_AGENTS: &[&str] = &[
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Safari/605.1.15",
+    "Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1",
+    "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Mobile Safari/537.36",
+    "Mozilla/5.0 (X11; Linux x86_64; rv:127.0) Gecko/20100101 Firefox/127.0",
+];
+
+const LANGUAGES: &[&str] = &[
+    "en-US", "en-GB", "de-DE", "fr-FR", "es-ES", "pt-BR", "ja-JP", "hi-IN",
+];
+
+const COUNTRIES: &[&str] = &["US", "GB", "DE", "FR", "ES", "BR", "JP", "IN", "NL", "AU"];
+
+const CONNECTORS: &[&str] = &[
+    "stripe",
+    "adyen",
+    "checkout",
+    "cybersource",
+    "paypal",
+    "braintree",
+    "worldpay",
+    "klarna",
+];
+
+const ISSUERS: &[&str] = &[
+    "JP Morgan Chase",
+    "Bank of America",
+    "Barclays",
+    "HSBC",
+    "Deutsche Bank",
+    "Santander",
+    "HDFC Bank",
+];
+
+/// Card networks with an IIN prefix and PAN length.
+const CARD_NETWORKS: &[(&str, &str, usize)] = &[
+    ("Visa", "4", 16),
+    ("Mastercard", "51", 16),
+    ("Mastercard", "55", 16),
+    ("AmericanExpress", "34", 15),
+    ("AmericanExpress", "37", 15),
+    ("Discover", "6011", 16),
+];
+
+/// Shape of Hyperswitch's `BrowserInformation`.
+pub fn browser_info(ctx: &mut Context) -> Value {
+    let rng = ctx.rng();
+
+    let value = json!({
+        "color_depth": pick(rng, &[24, 30, 32]),
+        "java_enabled": rng.gen_bool(0.1),
+        "java_script_enabled": rng.gen_bool(0.98),
+        "language": pick(rng, LANGUAGES),
+        "screen_height": pick(rng, &[720, 768, 900, 1080, 1440, 2160]),
+        "screen_width": pick(rng, &[390, 1280, 1366, 1536, 1920, 2560]),
+        "time_zone": pick(rng, &[-480, -300, 0, 60, 330, 540]),
+        "ip_address": format!(
+            "{}.{}.{}.{}",
+            rng.gen_range(1..224),
+            rng.gen::<u8>(),
+            rng.gen::<u8>(),
+            rng.gen_range(1..255)
+        ),
+        "accept_header": "text/html,application/xhtml+xml,application/xml;q=0.9,image/webp,*/*;q=0.8",
+        "user_agent": pick(rng, USER_AGENTS),
+    });
+
+    pad(ctx, value)
+}
+
+/// Card details as stored after tokenization: only the IIN and last four digits of the PAN
+/// survive, the rest is masked.
+pub fn payment_method_data(ctx: &mut Context) -> Value {
+    let rng = ctx.rng();
+
+    let (network, prefix, length) = pick(rng, CARD_NETWORKS);
+    let pan = format!("{prefix}{}", digits(rng, length - prefix.len()));
+    let isin = &pan[..6];
+    let last4 = &pan[length - 4..];
+
+    let value = json!({
+        "card": {
+            "last4": last4,
+            "card_isin": isin,
+            "masked_card_number": format!("{isin}{}{last4}", "*".repeat(length - 10)),
+            "card_network": network,
+            "card_type": pick(rng, &["CREDIT", "DEBIT"]),
+            "card_issuer": pick(rng, ISSUERS),
+            "card_issuing_country": pick(rng, COUNTRIES),
+            "card_exp_month": format!("{:02}", rng.gen_range(1..=12)),
+            "card_exp_year": rng.gen_range(2025..2035).to_string(),
+            "card_holder_name": pick(rng, &["John Doe", "Jane Roe", "Max Mustermann", "Priya Sharma"]),
+            "payment_checks": {
+                "cvc_check": pick(rng, &["pass", "fail", "unavailable"]),
+                "address_line1_check": pick(rng, &["pass", "fail", "unavailable"]),
+                "address_postal_code_check": pick(rng, &["pass", "fail", "unavailable"]),
+            },
+            "authentication_data": null,
+        }
+    });
+
+    pad(ctx, value)
+}
+
+/// Nested connector specific data: the order reference, checkout session and risk evaluation.
+pub fn connector_metadata(ctx: &mut Context) -> Value {
+    let connector = pick(ctx.rng(), CONNECTORS);
+    let order_id = randr::string(ctx, 24);
+    let session_id = randr::string(ctx, 32);
+
+    let rng = ctx.rng();
+    let rules = (0..rng.gen_range(1..4))
+        .map(|index| {
+            json!({
+                "id": format!("rule_{index}"),
+                "outcome": pick(rng, &["allow", "review", "block"]),
+                "weight": rng.gen_range(0..100),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let value = json!({
+        "connector": connector,
+        "order_id": order_id,
+        "session": {
+            "id": session_id,
+            "expires_in": rng.gen_range(300..3600),
+            "redirect": {
+                "method": pick(rng, &["GET", "POST"]),
+                "url": format!("https://{connector}.example.com/checkout/{session_id}"),
+            },
+        },
+        "risk": {
+            "score": rng.gen_range(0..100),
+            "rules": rules,
+        },
+    });
+
+    pad(ctx, value)
+}
+
+/// Result of a 3DS authentication.
+pub fn authentication_data(ctx: &mut Context) -> Value {
+    let rng = ctx.rng();
+
+    let value = json!({
+        "threeds_server_transaction_id": uuid(rng),
+        "ds_trans_id": uuid(rng),
+        "acs_trans_id": uuid(rng),
+        "message_version": pick(rng, &["2.1.0", "2.2.0", "2.3.1"]),
+        "trans_status": pick(rng, &["Y", "N", "A", "C", "R"]),
+        "eci": pick(rng, &["01", "02", "05", "06", "07"]),
+        "authentication_value": base64(rng, 28),
+    });
+
+    pad(ctx, value)
+}
+
+/// Routing algorithm in Hyperswitch's `StraightThroughAlgorithm` shape.
+pub fn straight_through_algorithm(ctx: &mut Context) -> Value {
+    let count = ctx.rng().gen_range(1..4);
+    let connectors = (0..count)
+        .map(|_| {
+            let connector = pick(ctx.rng(), CONNECTORS);
+            json!({
+                "connector": connector,
+                "merchant_connector_id": format!("mca_{}", randr::string(ctx, 20)),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let value = match connectors.as_slice() {
+        [connector] => json!({ "type": "single", "data": connector }),
+        _ => json!({ "type": "priority", "data": connectors }),
+    };
+
+    pad(ctx, value)
+}
+
+/// Flat merchant metadata, used for free-form JSON fields.
+pub fn metadata(ctx: &mut Context) -> Value {
+    let order_reference = randr::string(ctx, 16);
+    let rng = ctx.rng();
+
+    let value = json!({
+        "order_reference": order_reference,
+        "channel": pick(rng, &["web", "ios", "android", "pos"]),
+        "customer_segment": pick(rng, &["new", "returning", "vip"]),
+    });
+
+    pad(ctx, value)
+}
+
+/// Grows an object up to the configured payload size by adding an `extra` map of random
+/// strings. The target is approximate, within a few bytes.
+fn pad(ctx: &mut Context, mut value: Value) -> Value {
+    let Some(target) = ctx.payload_bytes() else {
+        return value;
+    };
+
+    // `"extra":{},` around the padding entries
+    let mut size = value.to_string().len() + 11;
+    let mut extra = Map::new();
+
+    while size < target {
+        let key = format!("k{}", extra.len());
+        let data = randr::string(ctx, (target - size).clamp(1, 64));
+
+        // `"key":"data",`
+        size += key.len() + data.len() + 6;
+        extra.insert(key, Value::String(data));
+    }
+
+    if let (Value::Object(object), false) = (&mut value, extra.is_empty()) {
+        object.insert("extra".to_string(), Value::Object(extra));
+    }
+
+    value
+}
+
+fn pick<R: Rng, T: Copy>(rng: &mut R, values: &[T]) -> T {
+    *values
+        .choose(rng)
+        .expect("Error while picking from an empty list")
+}
+
+fn digits<R: Rng>(rng: &mut R, len: usize) -> String {
+    (0..len)
+        .map(|_| char::from(b'0' + rng.gen_range(0..10)))
+        .collect()
+}
+
+fn uuid<R: Rng>(rng: &mut R) -> String {
+    format!(
+        "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+        rng.gen::<u32>(),
+        rng.gen::<u16>(),
+        rng.gen_range(0..0x1000),
+        rng.gen_range(0x8000..0xc000),
+        rng.gen_range(0..1u64 << 48),
+    )
+}
+
+fn base64<R: Rng>(rng: &mut R, len: usize) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    (0..len)
+        .map(|_| char::from(ALPHABET[rng.gen_range(0..ALPHABET.len())]))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOADS: &[fn(&mut Context) -> Value] = &[
+        browser_info,
+        payment_method_data,
+        connector_metadata,
+        authentication_data,
+        straight_through_algorithm,
+        metadata,
+    ];
+
+    #[test]
+    fn payloads_are_padded_to_the_target() {
+        for target in [512, 1024, 4096, 16384] {
+            let mut ctx = Context::new(Some(1));
+            ctx.set_payload_bytes(target);
+
+            for payload in PAYLOADS {
+                let size = payload(&mut ctx).to_string().len();
+                assert!(
+                    (target..target + 16).contains(&size),
+                    "{size} bytes for a target of {target}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn targets_below_the_document_leave_it_unpadded() {
+        let mut ctx = Context::new(Some(1));
+        ctx.set_payload_bytes(16);
+
+        for payload in PAYLOADS {
+            let value = payload(&mut ctx);
+            assert!(value.to_string().len() > 16);
+            assert!(value.get("extra").is_none());
+        }
+    }
+
+    #[test]
+    fn card_numbers_are_masked() {
+        let mut ctx = Context::new(Some(1));
+
+        for _ in 0..1000 {
+            let card = &payment_method_data(&mut ctx)["card"];
+            let field = |name: &str| card[name].as_str().unwrap().to_string();
+            let (isin, last4, masked) = (
+                field("card_isin"),
+                field("last4"),
+                field("masked_card_number"),
+            );
+
+            // The IIN starts with the prefix of the card's network
+            let &(_, _, length) = CARD_NETWORKS
+                .iter()
+                .find(|(network, prefix, _)| {
+                    *network == field("card_network") && isin.starts_with(prefix)
+                })
+                .unwrap();
+            assert_eq!(isin.len(), 6);
+            assert_eq!(last4.len(), 4);
+            assert!(isin
+                .chars()
+                .chain(last4.chars())
+                .all(|c| c.is_ascii_digit()));
+
+            assert_eq!(masked.len(), length);
+            assert_eq!(masked, format!("{isin}{}{last4}", "*".repeat(length - 10)));
+
+            let month = field("card_exp_month").parse::<u8>().unwrap();
+            assert_eq!(field("card_exp_month").len(), 2);
+            assert!((1..=12).contains(&month));
+        }
+    }
+}
@@ -0,0 +1,612 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Once, OnceLock};
+
+use axum::async_trait;
+use deadpool_postgres::{GenericClient, Manager, ManagerConfig, Pool, RecyclingMethod};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{NoTls, Row};
+use tracing::warn;
+
+use crate::consistency::Overrides;
+use crate::layout::{self, Column, Layout};
+use crate::store::PaymentAttemptStore;
+use crate::update::PaymentAttemptUpdate;
+use crate::PaymentAttempt;
+
+/// Attempts in a Postgres `payment_attempts` table, the store Hyperswitch runs on. The primary
+/// key and indexes stand in for the Cassandra partitioning and lookup tables, see
+/// `schema_postgres.sql`. Enums are stored by variant name and nested data as `jsonb`.
+pub struct Postgres {
+    pool: Pool,
+}
+
+type Param<'a> = Box<dyn ToSql + Sync + Send + 'a>;
+
+impl Postgres {
+    /// Opens a pool of up to `pool_size` connections and creates the table and its indexes.
+    /// `schema_postgres.sql` is not versioned like the Cassandra migrations: it only uses
+    /// `IF NOT EXISTS` and is run on every connect, so it cannot change an existing table and
+    /// `casec migrate` does not cover it.
+    pub async fn connect(url: &str, pool_size: usize) -> Result<Self, Box<dyn std::error::Error>> {
+        let manager = Manager::from_config(
+            url.parse()?,
+            NoTls,
+            ManagerConfig {
+                recycling_method: RecyclingMethod::Fast,
+            },
+        );
+        let pool = Pool::builder(manager).max_size(pool_size).build()?;
+
+        pool.get()
+            .await?
+            .batch_execute(include_str!("schema_postgres.sql"))
+            .await?;
+
+        Ok(Self { pool })
+    }
+
+    /// Runs a `SELECT *` with the given filter, ordered like a Cassandra partition.
+    async fn select(
+        &self,
+        filter: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<PaymentAttempt>, Box<dyn std::error::Error>> {
+        let client = self.pool.get().await?;
+        let statement = client
+            .prepare_cached(&format!(
+                "SELECT * FROM payment_attempts WHERE {filter} ORDER BY attempt_id"
+            ))
+            .await?;
+
+        client
+            .query(&statement, params)
+            .await?
+            .iter()
+            .map(from_row)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl PaymentAttemptStore for Postgres {
+    /// Postgres has no consistency levels, requested ones are ignored with a warning the first
+    /// time.
+    fn with_consistency(self: Arc<Self>, overrides: Overrides) -> Arc<dyn PaymentAttemptStore> {
+        static IGNORED: Once = Once::new();
+
+        if !overrides.is_empty() {
+            IGNORED.call_once(|| warn!("Consistency overrides are ignored with STORAGE=postgres"));
+        }
+
+        self
+    }
+
+    /// Upserts on the primary key, matching Cassandra's `INSERT`.
+    async fn insert(
+        &self,
+        payment_attempt: PaymentAttempt,
+    ) -> Result<PaymentAttempt, Box<dyn std::error::Error>> {
+        let client = self.pool.get().await?;
+
+        upsert(&client, &payment_attempt).await?;
+
+        Ok(payment_attempt)
+    }
+
+    /// Upserts every attempt within one transaction.
+    async fn insert_many(
+        &self,
+        payment_attempts: &[PaymentAttempt],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut client = self.pool.get().await?;
+        let transaction = client.transaction().await?;
+
+        for payment_attempt in payment_attempts {
+            upsert(&transaction, payment_attempt).await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get(
+        &self,
+        payment_id: &str,
+        attempt_id: &str,
+    ) -> Result<Option<PaymentAttempt>, Box<dyn std::error::Error>> {
+        let attempts = self
+            .select(
+                "payment_id = $1 AND attempt_id = $2",
+                &[&payment_id, &attempt_id],
+            )
+            .await?;
+
+        Ok(attempts.into_iter().next())
+    }
+
+    async fn list(
+        &self,
+        payment_id: &str,
+    ) -> Result<Vec<PaymentAttempt>, Box<dyn std::error::Error>> {
+        self.select("payment_id = $1", &[&payment_id]).await
+    }
+
+    async fn attempt_ids(
+        &self,
+        payment_id: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let client = self.pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT attempt_id FROM payment_attempts WHERE payment_id = $1 ORDER BY attempt_id",
+            )
+            .await?;
+
+        let rows = client.query(&statement, &[&payment_id]).await?;
+
+        let attempt_ids = rows
+            .iter()
+            .map(|row| row.try_get("attempt_id"))
+            .collect::<Result<_, _>>()?;
+
+        Ok(attempt_ids)
+    }
+
+    async fn list_by_merchant(
+        &self,
+        merchant_id: &str,
+        payment_id: &str,
+    ) -> Result<Vec<PaymentAttempt>, Box<dyn std::error::Error>> {
+        self.select(
+            "merchant_id = $1 AND payment_id = $2",
+            &[&merchant_id, &payment_id],
+        )
+        .await
+    }
+
+    async fn list_by_connector_transaction(
+        &self,
+        merchant_id: &str,
+        connector_transaction_id: &str,
+    ) -> Result<Vec<PaymentAttempt>, Box<dyn std::error::Error>> {
+        self.select(
+            "merchant_id = $1 AND connector_transaction_id = $2",
+            &[&merchant_id, &connector_transaction_id],
+        )
+        .await
+    }
+
+    /// Sets the columns the update carries in a single `UPDATE`, like Cassandra does, updating
+    /// a missing attempt is an error.
+    async fn update(
+        &self,
+        payment_id: &str,
+        attempt_id: &str,
+        update: PaymentAttemptUpdate,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (sql, mut params) = update_statement(update.columns(Layout::Native)?)?;
+        params.push(Box::new(payment_id));
+        params.push(Box::new(attempt_id));
+
+        let client = self.pool.get().await?;
+        let statement = client.prepare_cached(&sql).await?;
+
+        let params = params
+            .iter()
+            .map(|value| value.as_ref() as &(dyn ToSql + Sync))
+            .collect::<Vec<_>>();
+
+        if client.execute(&statement, &params).await? == 0 {
+            return Err("No rows found".into());
+        }
+
+        Ok(())
+    }
+
+    async fn delete(
+        &self,
+        payment_id: &str,
+        attempt_id: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let client = self.pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "DELETE FROM payment_attempts WHERE payment_id = $1 AND attempt_id = $2",
+            )
+            .await?;
+
+        client
+            .execute(&statement, &[&payment_id, &attempt_id])
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_payment(&self, payment_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let client = self.pool.get().await?;
+        let statement = client
+            .prepare_cached("DELETE FROM payment_attempts WHERE payment_id = $1")
+            .await?;
+
+        client.execute(&statement, &[&payment_id]).await?;
+
+        Ok(())
+    }
+}
+
+async fn upsert(
+    client: &impl GenericClient,
+    payment_attempt: &PaymentAttempt,
+) -> Result<(), Box<dyn std::error::Error>> {
+    static UPSERT: OnceLock<String> = OnceLock::new();
+
+    let columns = columns(payment_attempt)?;
+    // Column names do not depend on the attempt, so the first one builds the statement for all
+    let upsert = UPSERT.get_or_init(|| {
+        let names = columns.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        let placeholders = (1..=names.len())
+            .map(|index| format!("${index}"))
+            .collect::<Vec<_>>();
+        let assignments = names
+            .iter()
+            .filter(|name| !matches!(**name, "payment_id" | "attempt_id"))
+            .map(|name| format!("{name} = EXCLUDED.{name}"))
+            .collect::<Vec<_>>();
+
+        format!(
+            "INSERT INTO payment_attempts ({}) VALUES ({}) ON CONFLICT (payment_id, attempt_id) DO UPDATE SET {}",
+            names.join(", "),
+            placeholders.join(", "),
+            assignments.join(", "),
+        )
+    });
+
+    let statement = client.prepare_cached(upsert).await?;
+
+    let params = columns
+        .iter()
+        .map(|(_, value)| value.as_ref() as &(dyn ToSql + Sync))
+        .collect::<Vec<_>>();
+
+    client.execute(&statement, &params).await?;
+
+    Ok(())
+}
+
+/// `UPDATE` setting `columns`, as encoded for the native layout, with their values. The key is
+/// bound after them, as `payment_id` and `attempt_id`.
+fn update_statement<'a>(
+    columns: Vec<(&'static str, Column<'a>)>,
+) -> Result<(String, Vec<Param<'a>>), Box<dyn std::error::Error>> {
+    let types = schema();
+    let mut assignments = Vec::with_capacity(columns.len());
+    let mut params: Vec<Param<'a>> = Vec::with_capacity(columns.len() + 2);
+
+    for (name, column) in columns {
+        let param: Param<'a> = match (column, types.get(name).copied()) {
+            (Column::Text(value), Some("JSONB")) => {
+                Box::new(serde_json::from_str::<serde_json::Value>(&value)?)
+            }
+            (Column::Text(value), _) => Box::new(value),
+            (Column::BigInt(value), _) => Box::new(value),
+            (Column::SmallInt(value), _) => Box::new(value),
+            (Column::Boolean(value), _) => Box::new(value),
+            (Column::Timestamp(millis), _) => Box::new(layout::from_millis(millis)?),
+            #[cfg(feature = "cassandra")]
+            (Column::UserType(_), _) => {
+                return Err(format!("{name} cannot be updated in Postgres").into())
+            }
+        };
+
+        params.push(param);
+        assignments.push(format!("{name} = ${}", params.len()));
+    }
+
+    let sql = format!(
+        "UPDATE payment_attempts SET {} WHERE payment_id = ${} AND attempt_id = ${}",
+        assignments.join(", "),
+        params.len() + 1,
+        params.len() + 2,
+    );
+
+    Ok((sql, params))
+}
+
+/// Column types of `payment_attempts` by name, as declared in `schema_postgres.sql`.
+fn schema() -> &'static HashMap<&'static str, &'static str> {
+    static SCHEMA: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
+
+    SCHEMA.get_or_init(|| {
+        include_str!("schema_postgres.sql")
+            .split_once("CREATE TABLE IF NOT EXISTS payment_attempts (")
+            .and_then(|(_, table)| table.split_once(");"))
+            .map(|(columns, _)| columns)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let mut words = line.split_whitespace();
+                let name = words.next()?;
+                let kind = words.next()?.trim_end_matches(',');
+
+                (name != "PRIMARY").then_some((name, kind))
+            })
+            .collect()
+    })
+}
+
+/// Every column of the attempt with its value, in the order they are bound.
+fn columns(
+    attempt: &PaymentAttempt,
+) -> Result<Vec<(&'static str, Param<'_>)>, Box<dyn std::error::Error>> {
+    Ok(vec![
+        ("payment_id", Box::new(&attempt.payment_id)),
+        ("merchant_id", Box::new(&attempt.merchant_id)),
+        ("attempt_id", Box::new(&attempt.attempt_id)),
+        ("status", Box::new(layout::variant_name(&attempt.status)?)),
+        ("amount", Box::new(&attempt.amount)),
+        ("currency", Box::new(enum_opt(&attempt.currency)?)),
+        ("save_to_locker", Box::new(&attempt.save_to_locker)),
+        ("connector", Box::new(&attempt.connector)),
+        ("error_message", Box::new(&attempt.error_message)),
+        ("offer_amount", Box::new(&attempt.offer_amount)),
+        ("surcharge_amount", Box::new(&attempt.surcharge_amount)),
+        ("tax_amount", Box::new(&attempt.tax_amount)),
+        ("payment_method_id", Box::new(&attempt.payment_method_id)),
+        (
+            "payment_method",
+            Box::new(enum_opt(&attempt.payment_method)?),
+        ),
+        (
+            "connector_transaction_id",
+            Box::new(&attempt.connector_transaction_id),
+        ),
+        (
+            "capture_method",
+            Box::new(enum_opt(&attempt.capture_method)?),
+        ),
+        ("capture_on", Box::new(&attempt.capture_on)),
+        ("confirm", Box::new(&attempt.confirm)),
+        (
+            "authentication_type",
+            Box::new(enum_opt(&attempt.authentication_type)?),
+        ),
+        ("created_at", Box::new(&attempt.created_at)),
+        ("modified_at", Box::new(&attempt.modified_at)),
+        ("last_synced", Box::new(&attempt.last_synced)),
+        (
+            "cancellation_reason",
+            Box::new(&attempt.cancellation_reason),
+        ),
+        ("amount_to_capture", Box::new(&attempt.amount_to_capture)),
+        ("mandate_id", Box::new(&attempt.mandate_id)),
+        ("browser_info", Box::new(&attempt.browser_info)),
+        ("error_code", Box::new(&attempt.error_code)),
+        ("payment_token", Box::new(&attempt.payment_token)),
+        ("connector_metadata", Box::new(&attempt.connector_metadata)),
+        (
+            "payment_experience",
+            Box::new(enum_opt(&attempt.payment_experience)?),
+        ),
+        (
+            "payment_method_type",
+            Box::new(enum_opt(&attempt.payment_method_type)?),
+        ),
+        (
+            "payment_method_data",
+            Box::new(&attempt.payment_method_data),
+        ),
+        ("business_sub_label", Box::new(&attempt.business_sub_label)),
+        (
+            "straight_through_algorithm",
+            Box::new(&attempt.straight_through_algorithm),
+        ),
+        (
+            "preprocessing_step_id",
+            Box::new(&attempt.preprocessing_step_id),
+        ),
+        (
+            "mandate_details",
+            Box::new(json_opt(&attempt.mandate_details)?),
+        ),
+        ("error_reason", Box::new(&attempt.error_reason)),
+        (
+            "multiple_capture_count",
+            Box::new(&attempt.multiple_capture_count),
+        ),
+        (
+            "connector_response_reference_id",
+            Box::new(&attempt.connector_response_reference_id),
+        ),
+        ("amount_capturable", Box::new(&attempt.amount_capturable)),
+        ("updated_by", Box::new(&attempt.updated_by)),
+        (
+            "merchant_connector_id",
+            Box::new(&attempt.merchant_connector_id),
+        ),
+        (
+            "authentication_data",
+            Box::new(&attempt.authentication_data),
+        ),
+        ("encoded_data", Box::new(&attempt.encoded_data)),
+        ("unified_code", Box::new(&attempt.unified_code)),
+        ("unified_message", Box::new(&attempt.unified_message)),
+        ("net_amount", Box::new(&attempt.net_amount)),
+        (
+            "external_three_ds_authentication_attempted",
+            Box::new(&attempt.external_three_ds_authentication_attempted),
+        ),
+        (
+            "authentication_connector",
+            Box::new(&attempt.authentication_connector),
+        ),
+        ("authentication_id", Box::new(&attempt.authentication_id)),
+        ("mandate_data", Box::new(json_opt(&attempt.mandate_data)?)),
+        ("fingerprint_id", Box::new(&attempt.fingerprint_id)),
+        (
+            "payment_method_billing_address_id",
+            Box::new(&attempt.payment_method_billing_address_id),
+        ),
+        ("charge_id", Box::new(&attempt.charge_id)),
+        ("client_source", Box::new(&attempt.client_source)),
+        ("client_version", Box::new(&attempt.client_version)),
+    ])
+}
+
+fn from_row(row: &Row) -> Result<PaymentAttempt, Box<dyn std::error::Error>> {
+    Ok(PaymentAttempt {
+        payment_id: row.try_get("payment_id")?,
+        merchant_id: row.try_get("merchant_id")?,
+        attempt_id: row.try_get("attempt_id")?,
+        status: layout::from_variant_name(row.try_get("status")?)?,
+        amount: row.try_get("amount")?,
+        currency: get_enum_opt(row, "currency")?,
+        save_to_locker: row.try_get("save_to_locker")?,
+        connector: row.try_get("connector")?,
+        error_message: row.try_get("error_message")?,
+        offer_amount: row.try_get("offer_amount")?,
+        surcharge_amount: row.try_get("surcharge_amount")?,
+        tax_amount: row.try_get("tax_amount")?,
+        payment_method_id: row.try_get("payment_method_id")?,
+        payment_method: get_enum_opt(row, "payment_method")?,
+        connector_transaction_id: row.try_get("connector_transaction_id")?,
+        capture_method: get_enum_opt(row, "capture_method")?,
+        capture_on: row.try_get("capture_on")?,
+        confirm: row.try_get("confirm")?,
+        authentication_type: get_enum_opt(row, "authentication_type")?,
+        created_at: row.try_get("created_at")?,
+        modified_at: row.try_get("modified_at")?,
+        last_synced: row.try_get("last_synced")?,
+        cancellation_reason: row.try_get("cancellation_reason")?,
+        amount_to_capture: row.try_get("amount_to_capture")?,
+        mandate_id: row.try_get("mandate_id")?,
+        browser_info: row.try_get("browser_info")?,
+        error_code: row.try_get("error_code")?,
+        payment_token: row.try_get("payment_token")?,
+        connector_metadata: row.try_get("connector_metadata")?,
+        payment_experience: get_enum_opt(row, "payment_experience")?,
+        payment_method_type: get_enum_opt(row, "payment_method_type")?,
+        payment_method_data: row.try_get("payment_method_data")?,
+        business_sub_label: row.try_get("business_sub_label")?,
+        straight_through_algorithm: row.try_get("straight_through_algorithm")?,
+        preprocessing_step_id: row.try_get("preprocessing_step_id")?,
+        mandate_details: get_json_opt(row, "mandate_details")?,
+        error_reason: row.try_get("error_reason")?,
+        multiple_capture_count: row.try_get("multiple_capture_count")?,
+        connector_response_reference_id: row.try_get("connector_response_reference_id")?,
+        amount_capturable: row.try_get("amount_capturable")?,
+        updated_by: row.try_get("updated_by")?,
+        merchant_connector_id: row.try_get("merchant_connector_id")?,
+        authentication_data: row.try_get("authentication_data")?,
+        encoded_data: row.try_get("encoded_data")?,
+        unified_code: row.try_get("unified_code")?,
+        unified_message: row.try_get("unified_message")?,
+        net_amount: row.try_get("net_amount")?,
+        external_three_ds_authentication_attempted: row
+            .try_get("external_three_ds_authentication_attempted")?,
+        authentication_connector: row.try_get("authentication_connector")?,
+        authentication_id: row.try_get("authentication_id")?,
+        mandate_data: get_json_opt(row, "mandate_data")?,
+        fingerprint_id: row.try_get("fingerprint_id")?,
+        payment_method_billing_address_id: row.try_get("payment_method_billing_address_id")?,
+        charge_id: row.try_get("charge_id")?,
+        client_source: row.try_get("client_source")?,
+        client_version: row.try_get("client_version")?,
+    })
+}
+
+fn enum_opt<T: Serialize>(value: &Option<T>) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    value.as_ref().map(layout::variant_name).transpose()
+}
+
+fn json_opt<T: Serialize>(
+    value: &Option<T>,
+) -> Result<Option<serde_json::Value>, Box<dyn std::error::Error>> {
+    Ok(value.as_ref().map(serde_json::to_value).transpose()?)
+}
+
+fn get_enum_opt<T: DeserializeOwned>(
+    row: &Row,
+    name: &str,
+) -> Result<Option<T>, Box<dyn std::error::Error>> {
+    let value: Option<String> = row.try_get(name)?;
+    value.map(layout::from_variant_name).transpose()
+}
+
+fn get_json_opt<T: DeserializeOwned>(
+    row: &Row,
+    name: &str,
+) -> Result<Option<T>, Box<dyn std::error::Error>> {
+    let value: Option<serde_json::Value> = row.try_get(name)?;
+    Ok(value.map(serde_json::from_value).transpose()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::randr::Context;
+    use crate::Generator;
+
+    #[test]
+    fn schema_declares_every_column() {
+        let attempt = Generator::Random.generate(&mut Context::new(Some(1)));
+        let columns = columns(&attempt)
+            .unwrap()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+
+        assert_eq!(schema().len(), columns.len());
+        assert_eq!(
+            schema().keys().copied().collect::<BTreeSet<_>>(),
+            columns.into_iter().collect::<BTreeSet<_>>()
+        );
+    }
+
+    #[test]
+    fn updates_set_schema_columns() {
+        for update in crate::update::tests::updates() {
+            let (sql, params) = update_statement(update.columns(Layout::Native).unwrap()).unwrap();
+            let (assignments, key) = sql
+                .strip_prefix("UPDATE payment_attempts SET ")
+                .and_then(|sql| sql.split_once(" WHERE "))
+                .unwrap();
+
+            for assignment in assignments.split(", ") {
+                let (name, _) = assignment.split_once(" = ").unwrap();
+                assert!(schema().contains_key(name), "{name}");
+            }
+
+            assert_eq!(
+                key,
+                format!(
+                    "payment_id = ${} AND attempt_id = ${}",
+                    params.len() + 1,
+                    params.len() + 2
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn updates_leave_out_fields_without_a_value() {
+        let update = PaymentAttemptUpdate::CaptureUpdate {
+            amount_to_capture: None,
+            multiple_capture_count: Some(2),
+            updated_by: "capture".to_string(),
+        };
+
+        let (sql, params) = update_statement(update.columns(Layout::Native).unwrap()).unwrap();
+
+        assert_eq!(
+            sql,
+            "UPDATE payment_attempts SET multiple_capture_count = $1, updated_by = $2, \
+             modified_at = $3 WHERE payment_id = $4 AND attempt_id = $5"
+        );
+        assert_eq!(params.len(), 3);
+    }
+}
@@ -0,0 +1,202 @@
+use std::borrow::Cow;
+
+use cassandra_cpp::{CassResult, Statement};
+use tracing::{field, info_span, Instrument};
+
+use crate::cassandra::Cassandra;
+use crate::consistency::Consistency;
+
+/// A CQL statement together with the name it is traced under. Preparing, binding and executing
+/// through it gives each step its own span, so that time spent binding parameters can be told
+/// apart from time spent waiting on Cassandra.
+pub struct Query {
+    name: &'static str,
+    access: Access,
+    cql: &'static str,
+}
+
+/// Decides which of the configured consistency levels a query runs with.
+#[derive(Clone, Copy)]
+pub enum Access {
+    Read,
+    Write,
+    /// A write to an existing row, which can be made conditional on the row existing
+    Update,
+}
+
+pub const INSERT: Query = Query::write("insert", include_str!("insert_query.cql"));
+pub const INSERT_BY_MERCHANT: Query = Query::write(
+    "insert_by_merchant",
+    include_str!("insert_by_merchant_query.cql"),
+);
+pub const INSERT_BY_CONNECTOR_TRANSACTION: Query = Query::write(
+    "insert_by_connector_transaction",
+    include_str!("insert_by_connector_transaction_query.cql"),
+);
+pub const SELECT: Query = Query::read("select", include_str!("select_query.cql"));
+pub const SELECT_BY_MERCHANT: Query = Query::read(
+    "select_by_merchant",
+    include_str!("select_by_merchant_query.cql"),
+);
+pub const SELECT_BY_CONNECTOR_TRANSACTION: Query = Query::read(
+    "select_by_connector_transaction",
+    include_str!("select_by_connector_transaction_query.cql"),
+);
+pub const SELECT_MERCHANT: Query =
+    Query::read("select_merchant", include_str!("select_merchant_query.cql"));
+pub const SELECT_ATTEMPT_IDS: Query = Query::read(
+    "select_attempt_ids",
+    include_str!("select_attempt_ids_query.cql"),
+);
+pub const LIST: Query = Query::read("list", include_str!("list_query.cql"));
+pub const UPDATE_STATUS: Query =
+    Query::update("update_status", include_str!("update_status_query.cql"));
+pub const UPDATE_RESPONSE: Query =
+    Query::update("update_response", include_str!("update_response_query.cql"));
+pub const UPDATE_CONNECTOR_RESPONSE: Query = Query::update(
+    "update_connector_response",
+    include_str!("update_connector_response_query.cql"),
+);
+pub const UPDATE_CAPTURE: Query =
+    Query::update("update_capture", include_str!("update_capture_query.cql"));
+pub const UPDATE_AMOUNT_TO_CAPTURE: Query = Query::update(
+    "update_amount_to_capture",
+    include_str!("update_amount_to_capture_query.cql"),
+);
+pub const UPDATE_ERROR: Query =
+    Query::update("update_error", include_str!("update_error_query.cql"));
+pub const UPDATE_VOID: Query = Query::update("update_void", include_str!("update_void_query.cql"));
+pub const UPDATE_PREPROCESSING: Query = Query::update(
+    "update_preprocessing",
+    include_str!("update_preprocessing_query.cql"),
+);
+pub const UPDATE_AUTHENTICATION: Query = Query::update(
+    "update_authentication",
+    include_str!("update_authentication_query.cql"),
+);
+pub const DELETE: Query = Query::write("delete", include_str!("delete_query.cql"));
+pub const DELETE_PARTITION: Query = Query::write(
+    "delete_partition",
+    include_str!("delete_partition_query.cql"),
+);
+
+/// Queries rendered, and prepared unless running unprepared, at startup.
+pub const STATIC: &[Query] = &[
+    INSERT,
+    INSERT_BY_MERCHANT,
+    INSERT_BY_CONNECTOR_TRANSACTION,
+    SELECT,
+    SELECT_BY_MERCHANT,
+    SELECT_BY_CONNECTOR_TRANSACTION,
+    SELECT_MERCHANT,
+    SELECT_ATTEMPT_IDS,
+    LIST,
+    UPDATE_STATUS,
+    UPDATE_RESPONSE,
+    UPDATE_CONNECTOR_RESPONSE,
+    UPDATE_CAPTURE,
+    UPDATE_AMOUNT_TO_CAPTURE,
+    UPDATE_ERROR,
+    UPDATE_VOID,
+    UPDATE_PREPROCESSING,
+    UPDATE_AUTHENTICATION,
+    DELETE,
+    DELETE_PARTITION,
+];
+
+impl Query {
+    pub const fn read(name: &'static str, cql: &'static str) -> Self {
+        Self {
+            name,
+            access: Access::Read,
+            cql,
+        }
+    }
+
+    pub const fn write(name: &'static str, cql: &'static str) -> Self {
+        Self {
+            name,
+            access: Access::Write,
+            cql,
+        }
+    }
+
+    pub const fn update(name: &'static str, cql: &'static str) -> Self {
+        Self {
+            name,
+            access: Access::Update,
+            cql,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Query text, main table queries refer to the table as `{payment_attempts}`.
+    pub fn cql(&self) -> &'static str {
+        self.cql
+    }
+
+    /// Query text as sent to Cassandra. With `conditional_updates`, updates get `IF EXISTS` so
+    /// that they fail on missing rows instead of writing partial ones, at the cost of running
+    /// as lightweight transactions.
+    pub fn text(&self, conditional_updates: bool) -> Cow<'static, str> {
+        match (self.access, conditional_updates) {
+            (Access::Update, true) => Cow::Owned(format!(
+                "{} IF EXISTS;",
+                self.cql.trim_end().trim_end_matches(';')
+            )),
+            _ => Cow::Borrowed(self.cql),
+        }
+    }
+
+    pub fn statement(
+        &self,
+        cassandra: &Cassandra,
+    ) -> Result<Statement, Box<dyn std::error::Error>> {
+        let _span = info_span!("prepare", query = self.name).entered();
+
+        let mut statement = cassandra.statement(self);
+        let consistencies = cassandra.consistencies();
+        statement.set_consistency(self.consistency(cassandra).into())?;
+        if let Some(serial) = consistencies.serial {
+            statement.set_serial_consistency(serial.into())?;
+        }
+
+        Ok(statement)
+    }
+
+    /// Runs `bind` inside the bind span of this query.
+    pub fn bind(
+        &self,
+        bind: impl FnOnce() -> Result<(), Box<dyn std::error::Error>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let _span = info_span!("bind", query = self.name).entered();
+
+        bind()
+    }
+
+    pub async fn execute(
+        &self,
+        cassandra: &Cassandra,
+        statement: Statement,
+    ) -> cassandra_cpp::Result<CassResult> {
+        statement
+            .execute()
+            .instrument(info_span!(
+                "execute",
+                query = self.name,
+                consistency = %self.consistency(cassandra),
+                serial_consistency = cassandra.consistencies().serial.map(field::display),
+            ))
+            .await
+    }
+
+    fn consistency(&self, cassandra: &Cassandra) -> Consistency {
+        match self.access {
+            Access::Read => cassandra.consistencies().read,
+            Access::Write | Access::Update => cassandra.consistencies().write,
+        }
+    }
+}
@@ -1,25 +1,178 @@
-use rand::distributions::Alphanumeric;
-use rand::Rng;
-use time::PrimitiveDateTime;
+use std::any::TypeId;
+use std::collections::HashMap;
+
+use rand::distributions::uniform::{SampleRange, SampleUniform};
+use rand::distributions::{Alphanumeric, Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use time::{Date, Duration, Month, PrimitiveDateTime};
+
+pub use randr_derive::Randr;
+
+/// State shared by every generator. Seeding it makes a run reproducible: the same seed yields
+/// the same sequence of values, timestamps included.
+pub struct Context {
+    rng: StdRng,
+    clock: Clock,
+    /// Per-enum variant weights, enums without an entry are sampled uniformly
+    weights: HashMap<TypeId, WeightedIndex<f64>>,
+    payload_bytes: Option<usize>,
+}
+
+enum Clock {
+    Wall,
+    /// Starts at a fixed instant and moves forward by a random step on every read
+    Logical(PrimitiveDateTime),
+}
+
+impl Context {
+    pub fn new(seed: Option<u64>) -> Self {
+        let (rng, clock) = match seed {
+            Some(seed) => (
+                StdRng::seed_from_u64(seed),
+                Clock::Logical(
+                    Date::from_calendar_date(2024, Month::January, 1)
+                        .expect("Error while building the logical clock epoch")
+                        .midnight(),
+                ),
+            ),
+            None => (StdRng::from_entropy(), Clock::Wall),
+        };
+
+        Self {
+            rng,
+            clock,
+            weights: HashMap::new(),
+            payload_bytes: None,
+        }
+    }
+
+    /// Size that generated JSON payloads are padded up to, if any.
+    pub fn payload_bytes(&self) -> Option<usize> {
+        self.payload_bytes
+    }
+
+    pub fn set_payload_bytes(&mut self, payload_bytes: usize) {
+        self.payload_bytes = Some(payload_bytes);
+    }
+
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    pub fn now(&mut self) -> PrimitiveDateTime {
+        match &mut self.clock {
+            Clock::Wall => {
+                let utc_date_time = time::OffsetDateTime::now_utc();
+                PrimitiveDateTime::new(utc_date_time.date(), utc_date_time.time())
+            }
+            Clock::Logical(now) => {
+                *now += Duration::milliseconds(self.rng.gen_range(0..1000));
+                *now
+            }
+        }
+    }
+
+    /// Picks one of `T`'s variants, using the configured weights if there are any.
+    pub fn sample<T: Variants>(&mut self) -> T {
+        let index = match self.weights.get(&TypeId::of::<T>()) {
+            Some(weights) => weights.sample(&mut self.rng),
+            None => self.rng.gen_range(0..T::VARIANTS.len()),
+        };
+
+        T::VARIANTS[index]
+    }
+
+    /// Replaces uniform sampling of `T` with the given `(variant, weight)` pairs. Variants are
+    /// named as they are serialized.
+    pub fn set_weights<T: Variants + Serialize>(
+        &mut self,
+        weights: &[(&str, f64)],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let names = T::VARIANTS
+            .iter()
+            .map(|variant| match serde_json::to_value(variant)? {
+                serde_json::Value::String(name) => Ok(name),
+                value => Err(format!("Variant is not a unit variant: {value}").into()),
+            })
+            .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+
+        let mut resolved = vec![0.0; names.len()];
+        for (variant, weight) in weights {
+            let index = names
+                .iter()
+                .position(|name| name == variant)
+                .ok_or_else(|| format!("Unknown variant: {variant}"))?;
+
+            resolved[index] = *weight;
+        }
+
+        self.weights
+            .insert(TypeId::of::<T>(), WeightedIndex::new(resolved)?);
+
+        Ok(())
+    }
+}
+
+/// Random alphanumeric string of `len` characters.
+pub fn string(ctx: &mut Context, len: usize) -> String {
+    String::from_utf8(
+        ctx.rng()
+            .sample_iter(&Alphanumeric)
+            .take(len)
+            .collect::<Vec<_>>(),
+    )
+    .expect("Error while generating random string")
+}
+
+/// Value sampled uniformly from `range`.
+pub fn range<T: SampleUniform, R: SampleRange<T>>(ctx: &mut Context, range: R) -> T {
+    ctx.rng().gen_range(range)
+}
+
+/// `None` with probability `null_rate`, otherwise the output of `generate`.
+pub fn nullable<T>(
+    ctx: &mut Context,
+    null_rate: f64,
+    generate: impl FnOnce(&mut Context) -> T,
+) -> Option<T> {
+    match ctx.rng().gen_bool(null_rate) {
+        true => None,
+        false => Some(generate(ctx)),
+    }
+}
+
+/// Unit enums that can be sampled variant by variant.
+pub trait Variants: Copy + 'static {
+    const VARIANTS: &'static [Self];
+}
 
 pub trait Randr {
-    fn factory<F>(factory: F) -> Self
+    fn factory<F>(ctx: &mut Context, factory: F) -> Self
     where
-        F: FnOnce() -> Self,
+        F: FnOnce(&mut Context) -> Self,
         Self: Sized,
     {
-        factory()
+        factory(ctx)
     }
 
-    fn default() -> Self
+    fn default(ctx: &mut Context) -> Self
     where
         Self: Sized;
 
-    fn randr(factory: Option<fn() -> Self>, transformer: Option<fn(Self) -> Self>) -> Self
+    fn randr(
+        ctx: &mut Context,
+        factory: Option<fn(&mut Context) -> Self>,
+        transformer: Option<fn(Self) -> Self>,
+    ) -> Self
     where
         Self: Sized,
     {
-        let output = factory.map(Self::factory).unwrap_or_else(Self::default);
+        let output = match factory {
+            Some(factory) => Self::factory(ctx, factory),
+            None => Self::default(ctx),
+        };
 
         match transformer {
             Some(transformer) => transformer(output),
@@ -29,54 +182,80 @@ pub trait Randr {
 }
 
 impl Randr for String {
-    fn default() -> Self {
-        String::from_utf8(
-            rand::thread_rng()
-                .sample_iter(&Alphanumeric)
-                .take(30)
-                .collect::<Vec<_>>(),
-        )
-        .expect("Error while generating random string")
+    fn default(ctx: &mut Context) -> Self {
+        string(ctx, 30)
     }
 }
 
 impl Randr for i64 {
-    fn default() -> Self {
-        rand::thread_rng().gen_range(i64::MIN..i64::MAX)
+    fn default(ctx: &mut Context) -> Self {
+        ctx.rng().gen_range(i64::MIN..i64::MAX)
     }
 }
 
 impl Randr for i16 {
-    fn default() -> Self {
-        rand::thread_rng().gen_range(i16::MIN..i16::MAX)
+    fn default(ctx: &mut Context) -> Self {
+        ctx.rng().gen_range(i16::MIN..i16::MAX)
     }
 }
 
 impl<T: Randr> Randr for Option<T> {
-    fn default() -> Self {
-        bool::randr(None, None).then(|| T::default())
+    fn default(ctx: &mut Context) -> Self {
+        match bool::randr(ctx, None, None) {
+            true => Some(T::default(ctx)),
+            false => None,
+        }
     }
 }
 
 impl Randr for PrimitiveDateTime {
-    fn default() -> Self
+    fn default(ctx: &mut Context) -> Self
     where
         Self: Sized,
     {
-        let utc_date_time = time::OffsetDateTime::now_utc();
-        PrimitiveDateTime::new(utc_date_time.date(), utc_date_time.time())
+        ctx.now()
     }
 }
 
 impl Randr for serde_json::Value {
-    fn default() -> Self {
-        // Fix this
-        serde_json::Value::Null
+    fn default(ctx: &mut Context) -> Self {
+        crate::payloads::metadata(ctx)
     }
 }
 
 impl Randr for bool {
-    fn default() -> Self {
-        rand::thread_rng().gen_bool(0.5)
+    fn default(ctx: &mut Context) -> Self {
+        ctx.rng().gen_bool(0.5)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Generator;
+
+    /// Serialized attempts generated by a context seeded with `seed`.
+    fn run(seed: u64, generator: Generator) -> Vec<String> {
+        let mut ctx = Context::new(Some(seed));
+        ctx.set_payload_bytes(2048);
+
+        (0..100)
+            .map(|_| serde_json::to_string(&generator.generate(&mut ctx)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_generates_the_same_attempts() {
+        for generator in [Generator::Random, Generator::Consistent] {
+            assert_eq!(run(42, generator), run(42, generator));
+        }
+    }
+
+    #[test]
+    fn different_seeds_generate_different_attempts() {
+        for generator in [Generator::Random, Generator::Consistent] {
+            let (first, second) = (run(42, generator), run(43, generator));
+            assert!(first.iter().zip(&second).all(|(a, b)| a != b));
+        }
     }
 }
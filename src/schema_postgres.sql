@@ -0,0 +1,65 @@
+CREATE TABLE IF NOT EXISTS payment_attempts (
+  payment_id TEXT NOT NULL,
+  merchant_id TEXT NOT NULL,
+  attempt_id TEXT NOT NULL,
+  status TEXT NOT NULL,
+  amount BIGINT NOT NULL,
+  currency TEXT,
+  save_to_locker BOOLEAN,
+  connector TEXT,
+  error_message TEXT,
+  offer_amount BIGINT,
+  surcharge_amount BIGINT,
+  tax_amount BIGINT,
+  payment_method_id TEXT,
+  payment_method TEXT,
+  connector_transaction_id TEXT,
+  capture_method TEXT,
+  capture_on TIMESTAMP,
+  confirm BOOLEAN NOT NULL,
+  authentication_type TEXT,
+  created_at TIMESTAMP NOT NULL,
+  modified_at TIMESTAMP NOT NULL,
+  last_synced TIMESTAMP,
+  cancellation_reason TEXT,
+  amount_to_capture BIGINT,
+  mandate_id TEXT,
+  browser_info JSONB,
+  error_code TEXT,
+  payment_token TEXT,
+  connector_metadata JSONB,
+  payment_experience TEXT,
+  payment_method_type TEXT,
+  payment_method_data JSONB,
+  business_sub_label TEXT,
+  straight_through_algorithm JSONB,
+  preprocessing_step_id TEXT,
+  mandate_details JSONB,
+  error_reason TEXT,
+  multiple_capture_count SMALLINT,
+  connector_response_reference_id TEXT,
+  amount_capturable BIGINT NOT NULL,
+  updated_by TEXT NOT NULL,
+  merchant_connector_id TEXT,
+  authentication_data JSONB,
+  encoded_data TEXT,
+  unified_code TEXT,
+  unified_message TEXT,
+  net_amount BIGINT,
+  external_three_ds_authentication_attempted BOOLEAN,
+  authentication_connector TEXT,
+  authentication_id TEXT,
+  mandate_data JSONB,
+  fingerprint_id TEXT,
+  payment_method_billing_address_id TEXT,
+  charge_id TEXT,
+  client_source TEXT,
+  client_version TEXT,
+  PRIMARY KEY (payment_id, attempt_id)
+);
+
+CREATE INDEX IF NOT EXISTS payment_attempts_merchant_id_payment_id_index
+  ON payment_attempts (merchant_id, payment_id);
+
+CREATE INDEX IF NOT EXISTS payment_attempts_merchant_id_connector_transaction_id_index
+  ON payment_attempts (merchant_id, connector_transaction_id);
@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use hdrhistogram::Histogram;
+use serde::Serialize;
+use tokio::time::Instant;
+
+/// Largest latency that is tracked, anything slower is recorded as this value.
+const MAX_LATENCY_MICROS: u64 = 60_000_000;
+
+/// Operations that latencies are tracked for.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Insert,
+    BatchInsert,
+    PointRead,
+    PartitionScan,
+    /// Attempts of a payment found through the merchant lookup table
+    LookupByMerchant,
+    /// Attempts found through the connector transaction lookup table
+    LookupByConnectorTransaction,
+    Update,
+    Delete,
+}
+
+/// In-process latency histograms, one per operation, covering the window since the last reset.
+pub struct Stats {
+    window: Mutex<Window>,
+}
+
+struct Window {
+    started: Instant,
+    operations: BTreeMap<Operation, Recorder>,
+}
+
+/// Latency histogram and error count of one operation.
+pub struct Recorder {
+    latencies: Histogram<u64>,
+    errors: u64,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self {
+            latencies: Histogram::new_with_bounds(1, MAX_LATENCY_MICROS, 3)
+                .expect("Error while creating latency histogram"),
+            errors: 0,
+        }
+    }
+}
+
+impl Recorder {
+    /// Records the latency of a successful call, failed calls only count as errors.
+    pub fn record(&mut self, elapsed: Duration, ok: bool) {
+        match ok {
+            true => self
+                .latencies
+                .saturating_record(u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX)),
+            false => self.errors += 1,
+        }
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.latencies
+            .add(&other.latencies)
+            .expect("Error while merging latency histograms");
+        self.errors += other.errors;
+    }
+
+    pub fn summary(&self) -> Summary {
+        Summary {
+            count: self.latencies.len(),
+            errors: self.errors,
+            p50: self.latencies.value_at_quantile(0.5),
+            p90: self.latencies.value_at_quantile(0.9),
+            p99: self.latencies.value_at_quantile(0.99),
+            p999: self.latencies.value_at_quantile(0.999),
+            max: self.latencies.max(),
+        }
+    }
+}
+
+/// Latency summary of an operation, in microseconds. Failed calls are only counted in
+/// `errors`.
+#[derive(Serialize)]
+pub struct Summary {
+    pub count: u64,
+    pub errors: u64,
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+    pub p999: u64,
+    pub max: u64,
+}
+
+#[derive(Serialize)]
+pub struct Snapshot {
+    window_secs: f64,
+    operations: BTreeMap<Operation, Summary>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self {
+            window: Mutex::new(Window {
+                started: Instant::now(),
+                operations: BTreeMap::new(),
+            }),
+        }
+    }
+
+    /// Awaits `future` and records how long it took under `operation`.
+    pub async fn time<T, E>(
+        &self,
+        operation: Operation,
+        future: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, E> {
+        let start = Instant::now();
+        let output = future.await;
+        let elapsed = start.elapsed();
+
+        let mut window = self.window.lock().expect("Error while locking stats");
+        window
+            .operations
+            .entry(operation)
+            .or_default()
+            .record(elapsed, output.is_ok());
+
+        output
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let window = self.window.lock().expect("Error while locking stats");
+
+        let operations = window
+            .operations
+            .iter()
+            .map(|(operation, recorder)| (*operation, recorder.summary()))
+            .collect();
+
+        Snapshot {
+            window_secs: window.started.elapsed().as_secs_f64(),
+            operations,
+        }
+    }
+
+    /// Drops everything recorded so far and starts a new window.
+    pub fn reset(&self) {
+        let mut window = self.window.lock().expect("Error while locking stats");
+
+        window.started = Instant::now();
+        window.operations.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merged_recorders_summarise_both() {
+        let mut first = Recorder::default();
+        let mut second = Recorder::default();
+
+        for micros in 1..=900 {
+            first.record(Duration::from_micros(micros), true);
+        }
+        for micros in 901..=1000 {
+            second.record(Duration::from_micros(micros), true);
+        }
+        second.record(Duration::from_secs(1), false);
+
+        first.merge(&second);
+        let summary = first.summary();
+
+        assert_eq!(summary.count, 1000);
+        assert_eq!(summary.errors, 1);
+        assert_eq!(summary.p50, 500);
+        assert_eq!(summary.p90, 900);
+        assert_eq!(summary.max, 1000);
+    }
+
+    #[test]
+    fn latencies_beyond_the_bound_are_clamped() {
+        let mut recorder = Recorder::default();
+        recorder.record(Duration::from_secs(3600), true);
+
+        assert_eq!(recorder.summary().count, 1);
+        assert!(recorder.summary().max >= MAX_LATENCY_MICROS);
+    }
+}
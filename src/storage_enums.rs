@@ -1,9 +1,9 @@
 use serde::{Deserialize, Serialize};
 use time::PrimitiveDateTime;
 
-use crate::randr::Randr;
+use crate::randr::{Context, Randr};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize, Randr)]
 pub enum AttemptStatus {
     Started,
     AuthenticationFailed,
@@ -31,7 +31,43 @@ pub enum AttemptStatus {
     DeviceDataCollectionPending,
 }
 
-#[derive(Serialize, Deserialize)]
+impl AttemptStatus {
+    /// Statuses that end the attempt with an error
+    pub fn is_failure(self) -> bool {
+        matches!(
+            self,
+            Self::AuthenticationFailed
+                | Self::RouterDeclined
+                | Self::AuthorizationFailed
+                | Self::CaptureFailed
+                | Self::VoidFailed
+                | Self::Failure
+        )
+    }
+
+    /// Statuses in which (part of) the amount can still be captured
+    pub fn is_capturable(self) -> bool {
+        matches!(
+            self,
+            Self::Started
+                | Self::AuthenticationPending
+                | Self::AuthenticationSuccessful
+                | Self::Authorized
+                | Self::Authorizing
+                | Self::CodInitiated
+                | Self::CaptureInitiated
+                | Self::PartialChargedAndChargeable
+                | Self::Unresolved
+                | Self::Pending
+                | Self::PaymentMethodAwaited
+                | Self::ConfirmationAwaited
+                | Self::DeviceDataCollectionPending
+        )
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Serialize, Deserialize, Randr)]
 pub enum Currency {
     AED,
     ALL,
@@ -180,7 +216,7 @@ pub enum Currency {
     ZMW,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize, Randr)]
 pub enum PaymentMethod {
     Card,
     Token,
@@ -195,7 +231,7 @@ pub enum PaymentMethod {
     ProcessorToken,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize, Randr)]
 pub enum CaptureMethod {
     /// Post the payment authorization, the capture will be executed on the full amount immediately
     Automatic,
@@ -207,7 +243,7 @@ pub enum CaptureMethod {
     Scheduled,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize, Randr)]
 pub enum AuthenticationType {
     /// If the card is enrolled for 3DS authentication, the 3DS based authentication will be activated. The liability of chargeback shift to the issuer
     ThreeDs,
@@ -215,7 +251,7 @@ pub enum AuthenticationType {
     NoThreeDs,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize, Randr)]
 pub enum PaymentExperience {
     /// The URL to which the customer needs to be redirected for completing the payment.
     RedirectToUrl,
@@ -233,7 +269,7 @@ pub enum PaymentExperience {
     DisplayWaitScreen,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Copy, Serialize, Deserialize, Randr)]
 pub enum PaymentMethodType {
     Ach,
     Affirm,
@@ -330,13 +366,13 @@ pub enum PaymentMethodType {
     Mifinity,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, Randr)]
 pub enum MandateDataType {
     SingleUse(MandateAmountData),
     MultiUse(Option<MandateAmountData>),
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, Randr)]
 pub struct MandateAmountData {
     pub amount: i64,
     pub currency: Currency,
@@ -345,84 +381,90 @@ pub struct MandateAmountData {
     pub metadata: Option<serde_json::Value>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, Randr)]
 pub struct MandateDetails {
     pub update_mandate_id: Option<String>,
 }
 
-impl Randr for MandateDetails {
-    fn default() -> Self
-    where
-        Self: Sized,
-    {
-        Self {
-            update_mandate_id: Option::<String>::randr(None, None),
-        }
-    }
-}
+/// Applies a weight spec such as `AttemptStatus=Charged:8,Failure:1;Currency=USD:5,EUR:2`.
+/// Variants are named as they are serialized, variants left out of an enum's list are never
+/// sampled.
+pub fn apply_weights(ctx: &mut Context, spec: &str) -> Result<(), Box<dyn std::error::Error>> {
+    for entry in spec.split(';').filter(|entry| !entry.trim().is_empty()) {
+        let (name, weights) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid weight entry: {entry}"))?;
+
+        let weights = weights
+            .split(',')
+            .map(|weight| {
+                let (variant, weight) = weight
+                    .split_once(':')
+                    .ok_or_else(|| format!("Invalid weight: {weight}"))?;
 
-impl Randr for MandateAmountData {
-    fn default() -> Self
-    where
-        Self: Sized,
-    {
-        Self {
-            amount: i64::randr(None, None),
-            currency: Currency::randr(None, None),
-            start_date: Option::<PrimitiveDateTime>::randr(None, None),
-            end_date: Option::<PrimitiveDateTime>::randr(None, None),
-            metadata: Option::<serde_json::Value>::randr(None, None),
+                Ok((variant.trim(), weight.trim().parse::<f64>()?))
+            })
+            .collect::<Result<Vec<_>, Box<dyn std::error::Error>>>()?;
+
+        match name.trim() {
+            "AttemptStatus" => ctx.set_weights::<AttemptStatus>(&weights)?,
+            "Currency" => ctx.set_weights::<Currency>(&weights)?,
+            "PaymentMethod" => ctx.set_weights::<PaymentMethod>(&weights)?,
+            "CaptureMethod" => ctx.set_weights::<CaptureMethod>(&weights)?,
+            "AuthenticationType" => ctx.set_weights::<AuthenticationType>(&weights)?,
+            "PaymentExperience" => ctx.set_weights::<PaymentExperience>(&weights)?,
+            "PaymentMethodType" => ctx.set_weights::<PaymentMethodType>(&weights)?,
+            name => return Err(format!("Unknown enum in weights: {name}").into()),
         }
     }
-}
 
-impl Randr for Currency {
-    fn default() -> Self {
-        Currency::USD
-    }
+    Ok(())
 }
 
-impl Randr for AttemptStatus {
-    fn default() -> Self {
-        AttemptStatus::Started
-    }
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl Randr for PaymentMethod {
-    fn default() -> Self {
-        PaymentMethod::Card
+    fn name<T: Serialize>(value: T) -> String {
+        serde_json::to_string(&value).unwrap()
     }
-}
 
-impl Randr for CaptureMethod {
-    fn default() -> Self {
-        CaptureMethod::Automatic
-    }
-}
+    #[test]
+    fn weights_restrict_sampling_to_listed_variants() {
+        let mut ctx = Context::new(Some(1));
+        apply_weights(&mut ctx, "AttemptStatus=Charged:1; Currency=USD:3,EUR:1;").unwrap();
 
-impl Randr for AuthenticationType {
-    fn default() -> Self {
-        AuthenticationType::ThreeDs
+        for _ in 0..1000 {
+            assert_eq!(name(ctx.sample::<AttemptStatus>()), "\"Charged\"");
+            assert!(["\"USD\"", "\"EUR\""].contains(&name(ctx.sample::<Currency>()).as_str()));
+        }
     }
-}
 
-impl Randr for PaymentExperience {
-    fn default() -> Self {
-        PaymentExperience::RedirectToUrl
-    }
-}
+    #[test]
+    fn empty_spec_keeps_uniform_sampling() {
+        let mut ctx = Context::new(Some(1));
+        apply_weights(&mut ctx, "").unwrap();
 
-impl Randr for PaymentMethodType {
-    fn default() -> Self {
-        PaymentMethodType::CardRedirect
+        let sampled = (0..1000)
+            .map(|_| name(ctx.sample::<CaptureMethod>()))
+            .collect::<std::collections::HashSet<_>>();
+        assert!(sampled.len() > 1);
     }
-}
 
-impl Randr for MandateDataType {
-    fn default() -> Self {
-        match bool::randr(None, None) {
-            true => Self::MultiUse(Randr::randr(None, None)),
-            false => Self::SingleUse(Randr::randr(None, None)),
+    #[test]
+    fn invalid_specs_are_rejected() {
+        for spec in [
+            "AttemptStatus",
+            "AttemptStatus=Charged",
+            "AttemptStatus=Charged:many",
+            "AttemptStatus=Unknown:1",
+            "AttemptStatus=Charged:0",
+            "Unknown=Charged:1",
+        ] {
+            assert!(
+                apply_weights(&mut Context::new(Some(1)), spec).is_err(),
+                "{spec}"
+            );
         }
     }
 }
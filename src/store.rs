@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+
+use crate::consistency::Overrides;
+use crate::update::PaymentAttemptUpdate;
+use crate::PaymentAttempt;
+
+/// Storage the HTTP handlers and the load generator run against. Attempts are keyed by
+/// `(payment_id, attempt_id)`, and the attempts of a payment are listed ordered by `attempt_id`.
+///
+/// Every implementation treats missing keys the same way: reads come back empty, updates fail
+/// with `No rows found` without writing anything, and deletes succeed without doing anything.
+/// The exception is Cassandra without `CONDITIONAL_UPDATES`, where an update is the plain upsert
+/// Hyperswitch runs and writes a partial row.
+#[async_trait]
+pub trait PaymentAttemptStore: Send + Sync {
+    /// Handle that serves a single request with the consistency levels it asked for. Stores
+    /// without a notion of consistency hand back themselves.
+    fn with_consistency(self: Arc<Self>, overrides: Overrides) -> Arc<dyn PaymentAttemptStore>;
+
+    /// Writes the attempt, replacing any attempt stored under the same key.
+    async fn insert(
+        &self,
+        payment_attempt: PaymentAttempt,
+    ) -> Result<PaymentAttempt, Box<dyn std::error::Error>>;
+
+    /// Writes every attempt as `insert` would, in a single round trip or transaction where the
+    /// backend has one.
+    async fn insert_many(
+        &self,
+        payment_attempts: &[PaymentAttempt],
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// `None` when there is no attempt under the key.
+    async fn get(
+        &self,
+        payment_id: &str,
+        attempt_id: &str,
+    ) -> Result<Option<PaymentAttempt>, Box<dyn std::error::Error>>;
+
+    async fn list(
+        &self,
+        payment_id: &str,
+    ) -> Result<Vec<PaymentAttempt>, Box<dyn std::error::Error>>;
+
+    /// Ids of the attempts of a payment, without reading the attempts themselves.
+    async fn attempt_ids(
+        &self,
+        payment_id: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>>;
+
+    async fn list_by_merchant(
+        &self,
+        merchant_id: &str,
+        payment_id: &str,
+    ) -> Result<Vec<PaymentAttempt>, Box<dyn std::error::Error>>;
+
+    /// Attempts whose current connector transaction id is `connector_transaction_id`.
+    async fn list_by_connector_transaction(
+        &self,
+        merchant_id: &str,
+        connector_transaction_id: &str,
+    ) -> Result<Vec<PaymentAttempt>, Box<dyn std::error::Error>>;
+
+    /// Applies the update to an existing attempt, failing with `No rows found` when there is
+    /// none.
+    async fn update(
+        &self,
+        payment_id: &str,
+        attempt_id: &str,
+        update: PaymentAttemptUpdate,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Deleting a missing attempt is not an error.
+    async fn delete(
+        &self,
+        payment_id: &str,
+        attempt_id: &str,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Deletes every attempt of a payment.
+    async fn delete_payment(&self, payment_id: &str) -> Result<(), Box<dyn std::error::Error>>;
+}
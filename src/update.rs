@@ -0,0 +1,805 @@
+#[cfg(feature = "cassandra")]
+use cassandra_cpp::{BindRustType, Statement};
+use serde::{Deserialize, Serialize};
+use time::PrimitiveDateTime;
+
+#[cfg(feature = "cassandra")]
+use crate::cassandra::Cassandra;
+use crate::layout::{Column, Layout};
+#[cfg(feature = "cassandra")]
+use crate::query::{self, Query};
+use crate::storage_enums;
+use crate::PaymentAttempt;
+
+/// Partial updates applied to an existing payment attempt, modelled on Hyperswitch's
+/// `PaymentAttemptUpdate`. Optional fields are only written when present, every variant bumps
+/// `modified_at`.
+#[allow(clippy::large_enum_variant)]
+#[derive(Serialize, Deserialize)]
+pub enum PaymentAttemptUpdate {
+    StatusUpdate {
+        status: storage_enums::AttemptStatus,
+        updated_by: String,
+    },
+    ResponseUpdate {
+        status: storage_enums::AttemptStatus,
+        connector: Option<String>,
+        connector_transaction_id: Option<String>,
+        authentication_type: Option<storage_enums::AuthenticationType>,
+        payment_method_id: Option<String>,
+        mandate_id: Option<String>,
+        connector_metadata: Option<serde_json::Value>,
+        payment_token: Option<String>,
+        error_code: Option<String>,
+        error_message: Option<String>,
+        error_reason: Option<String>,
+        connector_response_reference_id: Option<String>,
+        amount_capturable: Option<i64>,
+        updated_by: String,
+        authentication_data: Option<serde_json::Value>,
+        encoded_data: Option<String>,
+        unified_code: Option<String>,
+        unified_message: Option<String>,
+        charge_id: Option<String>,
+    },
+    ConnectorResponse {
+        authentication_data: Option<serde_json::Value>,
+        encoded_data: Option<String>,
+        connector_transaction_id: Option<String>,
+        connector: Option<String>,
+        charge_id: Option<String>,
+        updated_by: String,
+    },
+    CaptureUpdate {
+        amount_to_capture: Option<i64>,
+        multiple_capture_count: Option<i16>,
+        updated_by: String,
+    },
+    AmountToCaptureUpdate {
+        status: storage_enums::AttemptStatus,
+        amount_capturable: i64,
+        updated_by: String,
+    },
+    ErrorUpdate {
+        connector: Option<String>,
+        status: storage_enums::AttemptStatus,
+        error_code: Option<String>,
+        error_message: Option<String>,
+        error_reason: Option<String>,
+        amount_capturable: Option<i64>,
+        updated_by: String,
+        unified_code: Option<String>,
+        unified_message: Option<String>,
+        connector_transaction_id: Option<String>,
+    },
+    VoidUpdate {
+        status: storage_enums::AttemptStatus,
+        cancellation_reason: Option<String>,
+        updated_by: String,
+    },
+    PreprocessingUpdate {
+        status: storage_enums::AttemptStatus,
+        payment_method_id: Option<String>,
+        connector_metadata: Option<serde_json::Value>,
+        preprocessing_step_id: Option<String>,
+        connector_transaction_id: Option<String>,
+        connector_response_reference_id: Option<String>,
+        updated_by: String,
+    },
+    AuthenticationUpdate {
+        status: storage_enums::AttemptStatus,
+        external_three_ds_authentication_attempted: Option<bool>,
+        authentication_connector: Option<String>,
+        authentication_id: Option<String>,
+        updated_by: String,
+    },
+}
+
+impl PaymentAttemptUpdate {
+    /// Binds the prepared `UPDATE` of this variant. Fields the update does not carry are left
+    /// unset, so their columns keep their values.
+    #[cfg(feature = "cassandra")]
+    pub fn statement(
+        &self,
+        cassandra: &Cassandra,
+        payment_id: &str,
+        attempt_id: &str,
+    ) -> Result<(&'static Query, Statement), Box<dyn std::error::Error>> {
+        let query = self.query();
+        let changes = self
+            .changes(cassandra.layout())?
+            .set_time("modified_at", &now())?;
+        let mut statement = query.statement(cassandra)?;
+
+        query.bind(|| {
+            let loc = changes.columns.len();
+
+            for (loc, (_, value)) in changes.columns.into_iter().enumerate() {
+                if let Some(value) = value {
+                    value.bind(&mut statement, loc)?;
+                }
+            }
+
+            statement.bind(loc, payment_id)?;
+            statement.bind(loc + 1, attempt_id)?;
+
+            Ok(())
+        })?;
+
+        Ok((query, statement))
+    }
+
+    /// The columns this update carries a value for, followed by `modified_at`, for stores that
+    /// build the statement per update. Fields without a value are left out, so their columns
+    /// keep their values like the unset ones of `statement`.
+    pub fn columns(
+        &self,
+        layout: Layout,
+    ) -> Result<Vec<(&'static str, Column<'_>)>, Box<dyn std::error::Error>> {
+        let changes = self.changes(layout)?.set_time("modified_at", &now())?;
+
+        Ok(changes
+            .columns
+            .into_iter()
+            .filter_map(|(name, value)| Some((name, value?)))
+            .collect())
+    }
+
+    /// Statement of this variant, setting the columns of `changes` in order followed by
+    /// `modified_at`.
+    #[cfg(feature = "cassandra")]
+    fn query(&self) -> &'static Query {
+        match self {
+            Self::StatusUpdate { .. } => &query::UPDATE_STATUS,
+            Self::ResponseUpdate { .. } => &query::UPDATE_RESPONSE,
+            Self::ConnectorResponse { .. } => &query::UPDATE_CONNECTOR_RESPONSE,
+            Self::CaptureUpdate { .. } => &query::UPDATE_CAPTURE,
+            Self::AmountToCaptureUpdate { .. } => &query::UPDATE_AMOUNT_TO_CAPTURE,
+            Self::ErrorUpdate { .. } => &query::UPDATE_ERROR,
+            Self::VoidUpdate { .. } => &query::UPDATE_VOID,
+            Self::PreprocessingUpdate { .. } => &query::UPDATE_PREPROCESSING,
+            Self::AuthenticationUpdate { .. } => &query::UPDATE_AUTHENTICATION,
+        }
+    }
+
+    /// Applies this variant to an attempt held in memory, writing the same fields as the
+    /// `UPDATE` statement does.
+    pub fn apply(self, attempt: &mut PaymentAttempt) {
+        match self {
+            Self::StatusUpdate { status, updated_by } => {
+                attempt.status = status;
+                attempt.updated_by = updated_by;
+            }
+            Self::ResponseUpdate {
+                status,
+                connector,
+                connector_transaction_id,
+                authentication_type,
+                payment_method_id,
+                mandate_id,
+                connector_metadata,
+                payment_token,
+                error_code,
+                error_message,
+                error_reason,
+                connector_response_reference_id,
+                amount_capturable,
+                updated_by,
+                authentication_data,
+                encoded_data,
+                unified_code,
+                unified_message,
+                charge_id,
+            } => {
+                attempt.status = status;
+                set_opt(&mut attempt.connector, connector);
+                set_opt(
+                    &mut attempt.connector_transaction_id,
+                    connector_transaction_id,
+                );
+                set_opt(&mut attempt.authentication_type, authentication_type);
+                set_opt(&mut attempt.payment_method_id, payment_method_id);
+                set_opt(&mut attempt.mandate_id, mandate_id);
+                set_opt(&mut attempt.connector_metadata, connector_metadata);
+                set_opt(&mut attempt.payment_token, payment_token);
+                set_opt(&mut attempt.error_code, error_code);
+                set_opt(&mut attempt.error_message, error_message);
+                set_opt(&mut attempt.error_reason, error_reason);
+                set_opt(
+                    &mut attempt.connector_response_reference_id,
+                    connector_response_reference_id,
+                );
+                if let Some(amount_capturable) = amount_capturable {
+                    attempt.amount_capturable = amount_capturable;
+                }
+                attempt.updated_by = updated_by;
+                set_opt(&mut attempt.authentication_data, authentication_data);
+                set_opt(&mut attempt.encoded_data, encoded_data);
+                set_opt(&mut attempt.unified_code, unified_code);
+                set_opt(&mut attempt.unified_message, unified_message);
+                set_opt(&mut attempt.charge_id, charge_id);
+            }
+            Self::ConnectorResponse {
+                authentication_data,
+                encoded_data,
+                connector_transaction_id,
+                connector,
+                charge_id,
+                updated_by,
+            } => {
+                set_opt(&mut attempt.authentication_data, authentication_data);
+                set_opt(&mut attempt.encoded_data, encoded_data);
+                set_opt(
+                    &mut attempt.connector_transaction_id,
+                    connector_transaction_id,
+                );
+                set_opt(&mut attempt.connector, connector);
+                set_opt(&mut attempt.charge_id, charge_id);
+                attempt.updated_by = updated_by;
+            }
+            Self::CaptureUpdate {
+                amount_to_capture,
+                multiple_capture_count,
+                updated_by,
+            } => {
+                set_opt(&mut attempt.amount_to_capture, amount_to_capture);
+                set_opt(&mut attempt.multiple_capture_count, multiple_capture_count);
+                attempt.updated_by = updated_by;
+            }
+            Self::AmountToCaptureUpdate {
+                status,
+                amount_capturable,
+                updated_by,
+            } => {
+                attempt.status = status;
+                attempt.amount_capturable = amount_capturable;
+                attempt.updated_by = updated_by;
+            }
+            Self::ErrorUpdate {
+                connector,
+                status,
+                error_code,
+                error_message,
+                error_reason,
+                amount_capturable,
+                updated_by,
+                unified_code,
+                unified_message,
+                connector_transaction_id,
+            } => {
+                set_opt(&mut attempt.connector, connector);
+                attempt.status = status;
+                set_opt(&mut attempt.error_code, error_code);
+                set_opt(&mut attempt.error_message, error_message);
+                set_opt(&mut attempt.error_reason, error_reason);
+                if let Some(amount_capturable) = amount_capturable {
+                    attempt.amount_capturable = amount_capturable;
+                }
+                attempt.updated_by = updated_by;
+                set_opt(&mut attempt.unified_code, unified_code);
+                set_opt(&mut attempt.unified_message, unified_message);
+                set_opt(
+                    &mut attempt.connector_transaction_id,
+                    connector_transaction_id,
+                );
+            }
+            Self::VoidUpdate {
+                status,
+                cancellation_reason,
+                updated_by,
+            } => {
+                attempt.status = status;
+                set_opt(&mut attempt.cancellation_reason, cancellation_reason);
+                attempt.updated_by = updated_by;
+            }
+            Self::PreprocessingUpdate {
+                status,
+                payment_method_id,
+                connector_metadata,
+                preprocessing_step_id,
+                connector_transaction_id,
+                connector_response_reference_id,
+                updated_by,
+            } => {
+                attempt.status = status;
+                set_opt(&mut attempt.payment_method_id, payment_method_id);
+                set_opt(&mut attempt.connector_metadata, connector_metadata);
+                set_opt(&mut attempt.preprocessing_step_id, preprocessing_step_id);
+                set_opt(
+                    &mut attempt.connector_transaction_id,
+                    connector_transaction_id,
+                );
+                set_opt(
+                    &mut attempt.connector_response_reference_id,
+                    connector_response_reference_id,
+                );
+                attempt.updated_by = updated_by;
+            }
+            Self::AuthenticationUpdate {
+                status,
+                external_three_ds_authentication_attempted,
+                authentication_connector,
+                authentication_id,
+                updated_by,
+            } => {
+                attempt.status = status;
+                set_opt(
+                    &mut attempt.external_three_ds_authentication_attempted,
+                    external_three_ds_authentication_attempted,
+                );
+                set_opt(
+                    &mut attempt.authentication_connector,
+                    authentication_connector,
+                );
+                set_opt(&mut attempt.authentication_id, authentication_id);
+                attempt.updated_by = updated_by;
+            }
+        }
+
+        attempt.modified_at = now();
+    }
+
+    /// The connector transaction id written by this update, if any. Needed to keep the
+    /// `(merchant_id, connector_transaction_id)` lookup table in sync.
+    #[cfg(feature = "cassandra")]
+    pub fn connector_transaction_id(&self) -> Option<&str> {
+        match self {
+            Self::ResponseUpdate {
+                connector_transaction_id,
+                ..
+            }
+            | Self::ConnectorResponse {
+                connector_transaction_id,
+                ..
+            }
+            | Self::ErrorUpdate {
+                connector_transaction_id,
+                ..
+            }
+            | Self::PreprocessingUpdate {
+                connector_transaction_id,
+                ..
+            } => connector_transaction_id.as_deref(),
+            Self::StatusUpdate { .. }
+            | Self::CaptureUpdate { .. }
+            | Self::AmountToCaptureUpdate { .. }
+            | Self::VoidUpdate { .. }
+            | Self::AuthenticationUpdate { .. } => None,
+        }
+    }
+
+    fn changes(&self, layout: Layout) -> Result<Changes<'_>, Box<dyn std::error::Error>> {
+        let changes = match self {
+            Self::StatusUpdate { status, updated_by } => Changes::new(layout)
+                .set_enum("status", status)?
+                .set("updated_by", updated_by.as_str()),
+            Self::ResponseUpdate {
+                status,
+                connector,
+                connector_transaction_id,
+                authentication_type,
+                payment_method_id,
+                mandate_id,
+                connector_metadata,
+                payment_token,
+                error_code,
+                error_message,
+                error_reason,
+                connector_response_reference_id,
+                amount_capturable,
+                updated_by,
+                authentication_data,
+                encoded_data,
+                unified_code,
+                unified_message,
+                charge_id,
+            } => Changes::new(layout)
+                .set_enum("status", status)?
+                .set_opt("connector", connector.as_deref())
+                .set_opt(
+                    "connector_transaction_id",
+                    connector_transaction_id.as_deref(),
+                )
+                .set_enum_opt("authentication_type", authentication_type)?
+                .set_opt("payment_method_id", payment_method_id.as_deref())
+                .set_opt("mandate_id", mandate_id.as_deref())
+                .set_json_opt("connector_metadata", connector_metadata)?
+                .set_opt("payment_token", payment_token.as_deref())
+                .set_opt("error_code", error_code.as_deref())
+                .set_opt("error_message", error_message.as_deref())
+                .set_opt("error_reason", error_reason.as_deref())
+                .set_opt(
+                    "connector_response_reference_id",
+                    connector_response_reference_id.as_deref(),
+                )
+                .set_opt("amount_capturable", *amount_capturable)
+                .set("updated_by", updated_by.as_str())
+                .set_json_opt("authentication_data", authentication_data)?
+                .set_opt("encoded_data", encoded_data.as_deref())
+                .set_opt("unified_code", unified_code.as_deref())
+                .set_opt("unified_message", unified_message.as_deref())
+                .set_opt("charge_id", charge_id.as_deref()),
+            Self::ConnectorResponse {
+                authentication_data,
+                encoded_data,
+                connector_transaction_id,
+                connector,
+                charge_id,
+                updated_by,
+            } => Changes::new(layout)
+                .set_json_opt("authentication_data", authentication_data)?
+                .set_opt("encoded_data", encoded_data.as_deref())
+                .set_opt(
+                    "connector_transaction_id",
+                    connector_transaction_id.as_deref(),
+                )
+                .set_opt("connector", connector.as_deref())
+                .set_opt("charge_id", charge_id.as_deref())
+                .set("updated_by", updated_by.as_str()),
+            Self::CaptureUpdate {
+                amount_to_capture,
+                multiple_capture_count,
+                updated_by,
+            } => Changes::new(layout)
+                .set_opt("amount_to_capture", *amount_to_capture)
+                .set_opt("multiple_capture_count", *multiple_capture_count)
+                .set("updated_by", updated_by.as_str()),
+            Self::AmountToCaptureUpdate {
+                status,
+                amount_capturable,
+                updated_by,
+            } => Changes::new(layout)
+                .set_enum("status", status)?
+                .set("amount_capturable", *amount_capturable)
+                .set("updated_by", updated_by.as_str()),
+            Self::ErrorUpdate {
+                connector,
+                status,
+                error_code,
+                error_message,
+                error_reason,
+                amount_capturable,
+                updated_by,
+                unified_code,
+                unified_message,
+                connector_transaction_id,
+            } => Changes::new(layout)
+                .set_opt("connector", connector.as_deref())
+                .set_enum("status", status)?
+                .set_opt("error_code", error_code.as_deref())
+                .set_opt("error_message", error_message.as_deref())
+                .set_opt("error_reason", error_reason.as_deref())
+                .set_opt("amount_capturable", *amount_capturable)
+                .set("updated_by", updated_by.as_str())
+                .set_opt("unified_code", unified_code.as_deref())
+                .set_opt("unified_message", unified_message.as_deref())
+                .set_opt(
+                    "connector_transaction_id",
+                    connector_transaction_id.as_deref(),
+                ),
+            Self::VoidUpdate {
+                status,
+                cancellation_reason,
+                updated_by,
+            } => Changes::new(layout)
+                .set_enum("status", status)?
+                .set_opt("cancellation_reason", cancellation_reason.as_deref())
+                .set("updated_by", updated_by.as_str()),
+            Self::PreprocessingUpdate {
+                status,
+                payment_method_id,
+                connector_metadata,
+                preprocessing_step_id,
+                connector_transaction_id,
+                connector_response_reference_id,
+                updated_by,
+            } => Changes::new(layout)
+                .set_enum("status", status)?
+                .set_opt("payment_method_id", payment_method_id.as_deref())
+                .set_json_opt("connector_metadata", connector_metadata)?
+                .set_opt("preprocessing_step_id", preprocessing_step_id.as_deref())
+                .set_opt(
+                    "connector_transaction_id",
+                    connector_transaction_id.as_deref(),
+                )
+                .set_opt(
+                    "connector_response_reference_id",
+                    connector_response_reference_id.as_deref(),
+                )
+                .set("updated_by", updated_by.as_str()),
+            Self::AuthenticationUpdate {
+                status,
+                external_three_ds_authentication_attempted,
+                authentication_connector,
+                authentication_id,
+                updated_by,
+            } => Changes::new(layout)
+                .set_enum("status", status)?
+                .set_opt(
+                    "external_three_ds_authentication_attempted",
+                    *external_three_ds_authentication_attempted,
+                )
+                .set_opt(
+                    "authentication_connector",
+                    authentication_connector.as_deref(),
+                )
+                .set_opt("authentication_id", authentication_id.as_deref())
+                .set("updated_by", updated_by.as_str()),
+        };
+
+        Ok(changes)
+    }
+}
+
+fn now() -> PrimitiveDateTime {
+    let utc_date_time = time::OffsetDateTime::now_utc();
+    PrimitiveDateTime::new(utc_date_time.date(), utc_date_time.time())
+}
+
+/// Leaves the field alone when the update does not carry a value for it.
+fn set_opt<T>(field: &mut Option<T>, value: Option<T>) {
+    if value.is_some() {
+        *field = value;
+    }
+}
+
+/// Columns written by an update, in the order they are bound. `None` leaves the column unset.
+struct Changes<'a> {
+    layout: Layout,
+    columns: Vec<(&'static str, Option<Column<'a>>)>,
+}
+
+impl<'a> Changes<'a> {
+    fn new(layout: Layout) -> Self {
+        Self {
+            layout,
+            columns: Vec::new(),
+        }
+    }
+
+    fn set(self, name: &'static str, value: impl Into<Column<'a>>) -> Self {
+        self.set_opt(name, Some(value))
+    }
+
+    fn set_opt(mut self, name: &'static str, value: Option<impl Into<Column<'a>>>) -> Self {
+        self.columns.push((name, value.map(Into::into)));
+        self
+    }
+
+    fn set_enum<T: Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let value = self.layout.enum_column(value)?;
+        Ok(self.set(name, value))
+    }
+
+    fn set_enum_opt<T: Serialize>(
+        self,
+        name: &'static str,
+        value: &Option<T>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let value = value
+            .as_ref()
+            .map(|value| self.layout.enum_column(value))
+            .transpose()?;
+        Ok(self.set_opt(name, value))
+    }
+
+    fn set_time(
+        self,
+        name: &'static str,
+        value: &PrimitiveDateTime,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let value = self.layout.time_column(value)?;
+        Ok(self.set(name, value))
+    }
+
+    /// JSON documents are stored as text in every layout.
+    fn set_json_opt(
+        self,
+        name: &'static str,
+        value: &Option<serde_json::Value>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let value = value.as_ref().map(serde_json::to_string).transpose()?;
+        Ok(self.set_opt(name, value))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use storage_enums::{AttemptStatus, AuthenticationType};
+
+    use super::*;
+
+    /// Every variant, with every optional field set.
+    pub(crate) fn updates() -> Vec<PaymentAttemptUpdate> {
+        let text = |name: &str| Some(format!("updated_{name}"));
+        let json = |name: &str| Some(serde_json::json!({ "updated": name }));
+
+        vec![
+            PaymentAttemptUpdate::StatusUpdate {
+                status: AttemptStatus::Charged,
+                updated_by: "status".to_string(),
+            },
+            PaymentAttemptUpdate::ResponseUpdate {
+                status: AttemptStatus::Authorized,
+                connector: text("connector"),
+                connector_transaction_id: text("connector_transaction_id"),
+                authentication_type: Some(AuthenticationType::ThreeDs),
+                payment_method_id: text("payment_method_id"),
+                mandate_id: text("mandate_id"),
+                connector_metadata: json("connector_metadata"),
+                payment_token: text("payment_token"),
+                error_code: text("error_code"),
+                error_message: text("error_message"),
+                error_reason: text("error_reason"),
+                connector_response_reference_id: text("connector_response_reference_id"),
+                amount_capturable: Some(4200),
+                updated_by: "response".to_string(),
+                authentication_data: json("authentication_data"),
+                encoded_data: text("encoded_data"),
+                unified_code: text("unified_code"),
+                unified_message: text("unified_message"),
+                charge_id: text("charge_id"),
+            },
+            PaymentAttemptUpdate::ConnectorResponse {
+                authentication_data: json("authentication_data"),
+                encoded_data: text("encoded_data"),
+                connector_transaction_id: text("connector_transaction_id"),
+                connector: text("connector"),
+                charge_id: text("charge_id"),
+                updated_by: "connector_response".to_string(),
+            },
+            PaymentAttemptUpdate::CaptureUpdate {
+                amount_to_capture: Some(4200),
+                multiple_capture_count: Some(3),
+                updated_by: "capture".to_string(),
+            },
+            PaymentAttemptUpdate::AmountToCaptureUpdate {
+                status: AttemptStatus::PartialCharged,
+                amount_capturable: 2100,
+                updated_by: "amount_to_capture".to_string(),
+            },
+            PaymentAttemptUpdate::ErrorUpdate {
+                connector: text("connector"),
+                status: AttemptStatus::Failure,
+                error_code: text("error_code"),
+                error_message: text("error_message"),
+                error_reason: text("error_reason"),
+                amount_capturable: Some(0),
+                updated_by: "error".to_string(),
+                unified_code: text("unified_code"),
+                unified_message: text("unified_message"),
+                connector_transaction_id: text("connector_transaction_id"),
+            },
+            PaymentAttemptUpdate::VoidUpdate {
+                status: AttemptStatus::Voided,
+                cancellation_reason: text("cancellation_reason"),
+                updated_by: "void".to_string(),
+            },
+            PaymentAttemptUpdate::PreprocessingUpdate {
+                status: AttemptStatus::Pending,
+                payment_method_id: text("payment_method_id"),
+                connector_metadata: json("connector_metadata"),
+                preprocessing_step_id: text("preprocessing_step_id"),
+                connector_transaction_id: text("connector_transaction_id"),
+                connector_response_reference_id: text("connector_response_reference_id"),
+                updated_by: "preprocessing".to_string(),
+            },
+            PaymentAttemptUpdate::AuthenticationUpdate {
+                status: AttemptStatus::AuthenticationSuccessful,
+                external_three_ds_authentication_attempted: Some(true),
+                authentication_connector: text("authentication_connector"),
+                authentication_id: text("authentication_id"),
+                updated_by: "authentication".to_string(),
+            },
+        ]
+    }
+
+    /// Columns set by an `UPDATE`, in the order they are bound.
+    #[cfg(feature = "cassandra")]
+    fn set_columns(cql: &str) -> Vec<&str> {
+        let start = cql.find(" SET ").unwrap() + " SET ".len();
+        let end = cql.find(" WHERE ").unwrap();
+
+        cql[start..end]
+            .split(',')
+            .map(|assignment| assignment.trim().trim_end_matches("= ?").trim())
+            .collect()
+    }
+
+    #[cfg(feature = "cassandra")]
+    #[test]
+    fn statements_set_the_changed_columns_in_order() {
+        let updates = updates();
+        let queries = updates
+            .iter()
+            .map(|update| update.query().name())
+            .collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(queries.len(), updates.len());
+
+        for update in updates {
+            let changes = update.changes(Layout::Text).unwrap();
+            let mut columns = changes
+                .columns
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>();
+            columns.push("modified_at");
+
+            assert_eq!(set_columns(update.query().cql()), columns);
+            assert!(!update.query().cql().contains("IF EXISTS"));
+            assert!(update
+                .query()
+                .text(true)
+                .ends_with("WHERE payment_id = ? AND attempt_id = ? IF EXISTS;"));
+        }
+    }
+
+    /// The update with every field that can be `None` cleared.
+    #[cfg(feature = "cassandra")]
+    fn without_optionals(update: &PaymentAttemptUpdate) -> PaymentAttemptUpdate {
+        let mut value = serde_json::to_value(update).unwrap();
+        let names = fields(&mut value).keys().cloned().collect::<Vec<_>>();
+
+        for name in names {
+            let mut cleared = value.clone();
+            fields(&mut cleared).insert(name, serde_json::Value::Null);
+
+            if serde_json::from_value::<PaymentAttemptUpdate>(cleared.clone()).is_ok() {
+                value = cleared;
+            }
+        }
+
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// Fields of a serialized variant.
+    #[cfg(feature = "cassandra")]
+    fn fields(value: &mut serde_json::Value) -> &mut serde_json::Map<String, serde_json::Value> {
+        value
+            .as_object_mut()
+            .and_then(|variant| variant.values_mut().next())
+            .and_then(serde_json::Value::as_object_mut)
+            .unwrap()
+    }
+
+    #[cfg(feature = "cassandra")]
+    #[test]
+    fn apply_writes_the_same_columns_as_the_statement() {
+        let mut ctx = crate::randr::Context::new(Some(5));
+        let updates = updates()
+            .into_iter()
+            .flat_map(|update| {
+                let cleared = without_optionals(&update);
+                [update, cleared]
+            })
+            .collect::<Vec<_>>();
+
+        for update in &updates {
+            let attempt = crate::Generator::Random.generate(&mut ctx);
+
+            let mut encoded = crate::tests::Encoded::new(&attempt, Layout::Text);
+            for (name, value) in update.changes(Layout::Text).unwrap().columns {
+                if let Some(value) = value {
+                    encoded.0.insert(name, Some(value));
+                }
+            }
+            let mut expected = PaymentAttempt::from_row(&encoded, Layout::Text).unwrap();
+
+            let mut applied = attempt.clone();
+            serde_json::from_value::<PaymentAttemptUpdate>(serde_json::to_value(update).unwrap())
+                .unwrap()
+                .apply(&mut applied);
+            assert!(applied.modified_at > attempt.modified_at);
+            expected.modified_at = applied.modified_at;
+
+            assert_eq!(
+                serde_json::to_value(&applied).unwrap(),
+                serde_json::to_value(&expected).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use cassandra_cpp::{DataType, LendingIterator, UserType, Value, ValueType};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::layout;
+use crate::storage_enums::{MandateAmountData, MandateDataType, MandateDetails};
+
+/// Types the native layout stores as a Cassandra user-defined type rather than as JSON, so that
+/// their fields can be read in cqlsh. The data types are built on the client and have to list
+/// the same fields, in the same order, as `migrations/0003_mandate_types.cql`.
+pub trait UserDefined: Serialize + DeserializeOwned {
+    fn to_user_type(&self) -> Result<UserType, Box<dyn std::error::Error>>;
+
+    fn from_user_type(value: &Value) -> Result<Self, Box<dyn std::error::Error>>;
+}
+
+fn mandate_amount_data() -> Result<DataType, Box<dyn std::error::Error>> {
+    let data_type = DataType::new_udt(5);
+    data_type.add_sub_value_type_by_name("amount", ValueType::BIGINT)?;
+    data_type.add_sub_value_type_by_name("currency", ValueType::TEXT)?;
+    data_type.add_sub_value_type_by_name("start_date", ValueType::TIMESTAMP)?;
+    data_type.add_sub_value_type_by_name("end_date", ValueType::TIMESTAMP)?;
+    data_type.add_sub_value_type_by_name("metadata", ValueType::TEXT)?;
+
+    Ok(data_type)
+}
+
+/// `MandateDataType` tagged by `kind`, which is the variant name. `amount_data` is only null for
+/// a `MultiUse` mandate without an amount.
+fn mandate_data_type() -> Result<DataType, Box<dyn std::error::Error>> {
+    let mut data_type = DataType::new_udt(2);
+    data_type.add_sub_value_type_by_name("kind", ValueType::TEXT)?;
+    data_type.add_sub_type_by_name("amount_data", mandate_amount_data()?)?;
+
+    Ok(data_type)
+}
+
+fn mandate_details() -> Result<DataType, Box<dyn std::error::Error>> {
+    let data_type = DataType::new_udt(1);
+    data_type.add_sub_value_type_by_name("update_mandate_id", ValueType::TEXT)?;
+
+    Ok(data_type)
+}
+
+impl UserDefined for MandateAmountData {
+    fn to_user_type(&self) -> Result<UserType, Box<dyn std::error::Error>> {
+        let mut user_type = mandate_amount_data()?.new_user_type();
+
+        user_type.set_int64_by_name("amount", self.amount)?;
+        user_type.set_string_by_name("currency", layout::variant_name(&self.currency)?.as_str())?;
+        for (name, date) in [
+            ("start_date", &self.start_date),
+            ("end_date", &self.end_date),
+        ] {
+            match date {
+                Some(date) => user_type.set_int64_by_name(name, layout::millis(date)?)?,
+                None => user_type.set_null_by_name(name)?,
+            };
+        }
+        match &self.metadata {
+            Some(metadata) => user_type
+                .set_string_by_name("metadata", serde_json::to_string(metadata)?.as_str())?,
+            None => user_type.set_null_by_name("metadata")?,
+        };
+
+        Ok(user_type)
+    }
+
+    fn from_user_type(value: &Value) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut amount = None;
+        let mut currency = None;
+        let mut start_date = None;
+        let mut end_date = None;
+        let mut metadata = None;
+
+        let mut fields = value.get_user_type()?;
+        while let Some((name, field)) = fields.next() {
+            if field.is_null() {
+                continue;
+            }
+
+            match name.as_str() {
+                "amount" => amount = Some(field.get_i64()?),
+                "currency" => currency = Some(layout::from_variant_name(field.get_string()?)?),
+                "start_date" => start_date = Some(layout::from_millis(field.get_i64()?)?),
+                "end_date" => end_date = Some(layout::from_millis(field.get_i64()?)?),
+                "metadata" => metadata = Some(serde_json::from_str(field.get_str()?)?),
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            amount: amount.ok_or("mandate_amount_data without an amount")?,
+            currency: currency.ok_or("mandate_amount_data without a currency")?,
+            start_date,
+            end_date,
+            metadata,
+        })
+    }
+}
+
+impl UserDefined for MandateDataType {
+    fn to_user_type(&self) -> Result<UserType, Box<dyn std::error::Error>> {
+        let mut user_type = mandate_data_type()?.new_user_type();
+
+        let (kind, amount_data) = match self {
+            Self::SingleUse(amount_data) => ("SingleUse", Some(amount_data)),
+            Self::MultiUse(amount_data) => ("MultiUse", amount_data.as_ref()),
+        };
+        user_type.set_string_by_name("kind", kind)?;
+        match amount_data {
+            Some(amount_data) => {
+                user_type.set_user_type_by_name("amount_data", amount_data.to_user_type()?)?
+            }
+            None => user_type.set_null_by_name("amount_data")?,
+        };
+
+        Ok(user_type)
+    }
+
+    fn from_user_type(value: &Value) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut kind = None;
+        let mut amount_data = None;
+
+        let mut fields = value.get_user_type()?;
+        while let Some((name, field)) = fields.next() {
+            if field.is_null() {
+                continue;
+            }
+
+            match name.as_str() {
+                "kind" => kind = Some(field.get_string()?),
+                "amount_data" => amount_data = Some(MandateAmountData::from_user_type(&field)?),
+                _ => {}
+            }
+        }
+
+        match kind.as_deref() {
+            Some("SingleUse") => Ok(Self::SingleUse(
+                amount_data.ok_or("SingleUse mandate without amount_data")?,
+            )),
+            Some("MultiUse") => Ok(Self::MultiUse(amount_data)),
+            Some(kind) => Err(format!("Unknown mandate kind: {kind}").into()),
+            None => Err("mandate_data_type without a kind".into()),
+        }
+    }
+}
+
+impl UserDefined for MandateDetails {
+    fn to_user_type(&self) -> Result<UserType, Box<dyn std::error::Error>> {
+        let mut user_type = mandate_details()?.new_user_type();
+
+        match &self.update_mandate_id {
+            Some(id) => user_type.set_string_by_name("update_mandate_id", id.as_str())?,
+            None => user_type.set_null_by_name("update_mandate_id")?,
+        };
+
+        Ok(user_type)
+    }
+
+    fn from_user_type(value: &Value) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut update_mandate_id = None;
+
+        let mut fields = value.get_user_type()?;
+        while let Some((name, field)) = fields.next() {
+            if name == "update_mandate_id" && !field.is_null() {
+                update_mandate_id = Some(field.get_string()?);
+            }
+        }
+
+        Ok(Self { update_mandate_id })
+    }
+}